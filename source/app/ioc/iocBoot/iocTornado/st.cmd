@@ -17,7 +17,15 @@ dbLoadTemplate("db/ai.substitutions", "PREFIX=${PREFIX}")
 dbLoadRecords("db/ao.db", "PREFIX=${PREFIX}")
 dbLoadRecords("db/di.db", "PREFIX=${PREFIX}")
 dbLoadRecords("db/do.db", "PREFIX=${PREFIX}")
+dbLoadRecords("db/skifio.db", "PREFIX=${PREFIX}")
+dbLoadTemplate("db/skifio_status.substitutions", "PREFIX=${PREFIX}")
+dbLoadRecords("db/loop.db", "PREFIX=${PREFIX}")
+dbLoadTemplate("db/task.substitutions", "PREFIX=${PREFIX}")
+dbLoadTemplate("db/rate.substitutions", "PREFIX=${PREFIX}")
+dbLoadRecords("db/connection.db", "PREFIX=${PREFIX}")
+dbLoadTemplate("db/msg_count.substitutions", "PREFIX=${PREFIX}")
 dbLoadRecords("db/debug.db", "PREFIX=${PREFIX},VERSION='${VERSION}',BUILD_DATE='${BUILD_DATE}'")
+dbLoadTemplate("db/stats_reset.substitutions", "PREFIX=${PREFIX}")
 
 cd "${TOP}/iocBoot/${IOC}"
 iocInit()
@@ -6,5 +6,9 @@ export LD_LIBRARY_PATH=/opt/epics_base/lib/$ARCH:/opt/ioc/lib/$ARCH
 
 source /opt/env.sh
 
+# RPMSG endpoint devices are numbered in order the MCU announces its endpoints.
+# Set explicit mapping if it doesn't match channel ids, e.g.:
+# export TORNADO_RPMSG_CHANNELS="0=/dev/rpmsg1,1=/dev/rpmsg0"
+
 cd /opt/ioc/iocBoot/iocTornado &&
 /opt/ioc/bin/$ARCH/Tornado st.cmd
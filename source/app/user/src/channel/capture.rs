@@ -0,0 +1,148 @@
+use super::{Channel, MsgRead, MsgWrite};
+use common::{
+    capture::{Direction, RecordHeader, MAGIC},
+    config,
+};
+use futures::ready;
+use std::{
+    env,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+/// Writes messages passed through channels to capture file.
+pub struct Recorder {
+    file: Mutex<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        file.write_all(&MAGIC)?;
+        Ok(Self {
+            file: Mutex::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    /// Create recorder if capture path is set in environment.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var_os(config::CAPTURE_ENV)?;
+        match Self::create(&path) {
+            Ok(recorder) => {
+                log::info!("Capture messages to {:?}", path);
+                Some(recorder)
+            }
+            Err(err) => {
+                log::error!("Cannot create capture {:?}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    fn record(&self, direction: Direction, channel: u8, msg: &[u8]) {
+        let header = RecordHeader {
+            time_ns: self.start.elapsed().as_nanos() as u64,
+            direction,
+            channel,
+            len: msg.len() as u16,
+        };
+        let mut record = Vec::with_capacity(RecordHeader::SIZE + msg.len());
+        record.extend_from_slice(&header.to_bytes());
+        record.extend_from_slice(msg);
+        // Record is written at once to keep capture readable if IOC crashes.
+        if let Err(err) = self.file.lock().unwrap().write_all(&record) {
+            log::error!("Cannot write capture: {}", err);
+        }
+    }
+}
+
+/// Channel that records all messages passed through it.
+pub struct Captured<C: Channel> {
+    inner: C,
+    id: u8,
+    recorder: Option<Arc<Recorder>>,
+}
+
+pub struct CapturedRead<R: MsgRead + Unpin> {
+    inner: R,
+    id: u8,
+    recorder: Option<Arc<Recorder>>,
+}
+
+pub struct CapturedWrite<W: MsgWrite + Unpin> {
+    inner: W,
+    id: u8,
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl<C: Channel> Captured<C> {
+    /// Messages are passed as is if `recorder` is `None`.
+    pub fn new(inner: C, id: u32, recorder: Option<Arc<Recorder>>) -> Self {
+        Self {
+            inner,
+            id: id as u8,
+            recorder,
+        }
+    }
+}
+
+impl<C: Channel> Channel for Captured<C> {
+    type Read = CapturedRead<C::Read>;
+    type Write = CapturedWrite<C::Write>;
+    fn split(self) -> (Self::Read, Self::Write) {
+        let (r, w) = self.inner.split();
+        (
+            CapturedRead {
+                inner: r,
+                id: self.id,
+                recorder: self.recorder.clone(),
+            },
+            CapturedWrite {
+                inner: w,
+                id: self.id,
+                recorder: self.recorder,
+            },
+        )
+    }
+}
+
+impl<R: MsgRead + Unpin> MsgRead for CapturedRead<R> {
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let len = ready!(Pin::new(&mut this.inner).poll_recv(cx, buf))?;
+        if let Some(recorder) = &this.recorder {
+            if len > 0 {
+                recorder.record(Direction::McuToApp, this.id, &buf[..len]);
+            }
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<W: MsgWrite + Unpin> MsgWrite for CapturedWrite<W> {
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_ready(cx)
+    }
+    fn start_send(self: Pin<&mut Self>, msg: &[u8]) -> io::Result<()> {
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).start_send(msg)?;
+        if let Some(recorder) = &this.recorder {
+            recorder.record(Direction::AppToMcu, this.id, msg);
+        }
+        Ok(())
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+}
@@ -0,0 +1,151 @@
+use super::{MsgRead, MsgWrite};
+use futures::ready;
+use std::{
+    io,
+    mem::size_of,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Messages are sent over byte stream prefixed with their length.
+type FrameLen = u16;
+const HEADER_LEN: usize = size_of::<FrameLen>();
+
+/// Receives length-prefixed messages from byte stream.
+pub struct FramedRead<R: AsyncRead + Unpin> {
+    inner: R,
+    header: [u8; HEADER_LEN],
+    /// Number of bytes of current frame received so far.
+    pos: usize,
+    /// Frame boundaries are lost, so stream cannot be read anymore.
+    broken: bool,
+}
+
+/// Sends length-prefixed messages to byte stream.
+pub struct FramedWrite<W: AsyncWrite + Unpin> {
+    inner: W,
+    /// Current frame with header, empty if there is no frame being sent.
+    frame: Vec<u8>,
+    /// Number of bytes of current frame sent so far.
+    pos: usize,
+}
+
+impl<R: AsyncRead + Unpin> FramedRead<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            header: [0; HEADER_LEN],
+            pos: 0,
+            broken: false,
+        }
+    }
+}
+
+fn poll_read_some<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    dst: &mut [u8],
+) -> Poll<io::Result<usize>> {
+    let mut dst = ReadBuf::new(dst);
+    ready!(Pin::new(reader).poll_read(cx, &mut dst))?;
+    Poll::Ready(Ok(dst.filled().len()))
+}
+
+impl<R: AsyncRead + Unpin> MsgRead for FramedRead<R> {
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.broken {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Frame boundaries are lost",
+            )));
+        }
+        while this.pos < HEADER_LEN {
+            let n = ready!(poll_read_some(
+                &mut this.inner,
+                cx,
+                &mut this.header[this.pos..]
+            ))?;
+            if n == 0 {
+                return Poll::Ready(if this.pos == 0 {
+                    Ok(0)
+                } else {
+                    Err(io::ErrorKind::UnexpectedEof.into())
+                });
+            }
+            this.pos += n;
+        }
+        let len = FrameLen::from_le_bytes(this.header) as usize;
+        if len > buf.len() {
+            this.pos = 0;
+            this.broken = true;
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Message is too long",
+            )));
+        }
+        while this.pos < HEADER_LEN + len {
+            let n = ready!(poll_read_some(
+                &mut this.inner,
+                cx,
+                &mut buf[(this.pos - HEADER_LEN)..len]
+            ))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            this.pos += n;
+        }
+        this.pos = 0;
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> FramedWrite<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            frame: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> MsgWrite for FramedWrite<W> {
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+    fn start_send(self: Pin<&mut Self>, msg: &[u8]) -> io::Result<()> {
+        let this = self.get_mut();
+        if !this.frame.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Previous message is not sent yet",
+            ));
+        }
+        let header = FrameLen::try_from(msg.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Message is too long"))?
+            .to_le_bytes();
+        this.frame.extend_from_slice(&header);
+        this.frame.extend_from_slice(msg);
+        this.pos = 0;
+        Ok(())
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while this.pos < this.frame.len() {
+            let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &this.frame[this.pos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            this.pos += n;
+        }
+        this.frame.clear();
+        this.pos = 0;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+}
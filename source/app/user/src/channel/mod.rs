@@ -1,12 +1,26 @@
 #[cfg(feature = "tcp")]
-use std::{io, time::Duration};
-use tokio::io::{AsyncRead as Read, AsyncWrite as Write};
+use std::io;
+#[cfg(feature = "unix")]
+use std::path::Path;
 #[cfg(feature = "tcp")]
-use tokio::{net::ToSocketAddrs, time::sleep};
+use tokio::net::ToSocketAddrs;
 
+pub mod capture;
+#[cfg(feature = "tcp")]
+mod framed;
+pub mod msg;
+pub mod replay;
+
+pub use capture::{Captured, Recorder};
+#[cfg(feature = "tcp")]
+pub use framed::{FramedRead, FramedWrite};
+pub use msg::{MsgRead, MsgWrite};
+pub use replay::Replay;
+
+/// Bidirectional channel that preserves message boundaries.
 pub trait Channel: 'static {
-    type Read: Read + Unpin + Send;
-    type Write: Write + Unpin + Send;
+    type Read: MsgRead + Unpin + Send;
+    type Write: MsgWrite + Unpin + Send;
     fn split(self) -> (Self::Read, Self::Write);
 }
 
@@ -17,28 +31,44 @@ pub use tokio::net::{
 };
 #[cfg(feature = "tcp")]
 impl Channel for TcpStream {
-    type Read = OwnedReadHalf;
-    type Write = OwnedWriteHalf;
+    type Read = FramedRead<OwnedReadHalf>;
+    type Write = FramedWrite<OwnedWriteHalf>;
     fn split(self) -> (Self::Read, Self::Write) {
-        self.into_split()
+        let (r, w) = self.into_split();
+        (FramedRead::new(r), FramedWrite::new(w))
     }
 }
 
 #[cfg(feature = "tcp")]
-pub async fn connect<A: ToSocketAddrs + Clone>(addr: A) -> Result<TcpStream, io::Error> {
-    loop {
-        match TcpStream::connect(addr.clone()).await {
-            Ok(socket) => return Ok(socket),
-            Err(err) => match err.kind() {
-                io::ErrorKind::ConnectionRefused => {
-                    sleep(Duration::from_millis(100)).await;
-                }
-                _ => return Err(err),
-            },
-        }
+pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<TcpStream, io::Error> {
+    TcpStream::connect(addr).await
+}
+
+#[cfg(any(feature = "unix", feature = "loopback"))]
+pub use tokio::net::UnixStream;
+#[cfg(any(feature = "unix", feature = "loopback"))]
+impl Channel for UnixStream {
+    type Read = FramedRead<tokio::net::unix::OwnedReadHalf>;
+    type Write = FramedWrite<tokio::net::unix::OwnedWriteHalf>;
+    fn split(self) -> (Self::Read, Self::Write) {
+        let (r, w) = self.into_split();
+        (FramedRead::new(r), FramedWrite::new(w))
     }
 }
 
+#[cfg(feature = "unix")]
+pub async fn connect_unix<P: AsRef<Path>>(path: P) -> Result<UnixStream, io::Error> {
+    UnixStream::connect(path).await
+}
+
+/// Connect to fake MCU running in the same process.
+#[cfg(feature = "loopback")]
+pub async fn connect_loopback(id: u32) -> Result<UnixStream, io::Error> {
+    let stream = mcu::channel::connect(id)?;
+    stream.set_nonblocking(true)?;
+    UnixStream::from_std(stream)
+}
+
 #[cfg(feature = "rpmsg")]
 pub mod rpmsg;
 #[cfg(feature = "rpmsg")]
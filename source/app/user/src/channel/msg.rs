@@ -0,0 +1,140 @@
+use common::buffer::Buffer;
+use flatty::{Emplacer, Flat};
+use futures::future::poll_fn;
+use std::{
+    io,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+
+/// Receiving half of channel that preserves message boundaries.
+pub trait MsgRead {
+    /// Receive a single message into `buf` and return its length, `0` means end of stream.
+    ///
+    /// If `Poll::Pending` is returned then the next call must be made with the same `buf`.
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+/// Sending half of channel that preserves message boundaries.
+///
+/// Message is sent completely once started, even if the future waiting for it is cancelled,
+/// so the channel stays usable after cancellation.
+pub trait MsgWrite {
+    /// Wait until channel is ready to start sending a new message.
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+    /// Start sending `msg` as a single message.
+    ///
+    /// Must be called only after `poll_ready` returned `Poll::Ready(Ok(()))`,
+    /// otherwise error of kind [`io::ErrorKind::WouldBlock`] is returned.
+    fn start_send(self: Pin<&mut Self>, msg: &[u8]) -> io::Result<()>;
+    /// Wait until all started messages are sent.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+#[derive(Debug, Error)]
+pub enum ReadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Cannot parse message: {0:?}")]
+    Parse(flatty::Error),
+    #[error("End of stream")]
+    Eof,
+}
+
+/// Reads typed messages from channel.
+pub struct MsgReader<M: Flat + ?Sized, R: MsgRead + Unpin> {
+    channel: R,
+    buffer: Buffer,
+    _p: PhantomData<M>,
+}
+
+/// Writes typed messages to channel.
+pub struct MsgWriter<M: Flat + ?Sized, W: MsgWrite + Unpin> {
+    channel: W,
+    buffer: Buffer,
+    _p: PhantomData<M>,
+}
+
+pub struct UninitWriteGuard<'a, M: Flat + ?Sized, W: MsgWrite + Unpin> {
+    writer: &'a mut MsgWriter<M, W>,
+}
+
+pub struct WriteGuard<'a, M: Flat + ?Sized, W: MsgWrite + Unpin> {
+    writer: &'a mut MsgWriter<M, W>,
+}
+
+impl<M: Flat + ?Sized, R: MsgRead + Unpin> MsgReader<M, R> {
+    pub fn new(channel: R, max_msg_len: usize) -> Self {
+        Self {
+            channel,
+            buffer: Buffer::for_msg::<M>(max_msg_len),
+            _p: PhantomData,
+        }
+    }
+
+    pub async fn read_message(&mut self) -> Result<&M, ReadError> {
+        let (channel, buffer) = (&mut self.channel, &mut self.buffer);
+        let len = poll_fn(|cx| Pin::new(&mut *channel).poll_recv(cx, buffer)).await?;
+        if len == 0 {
+            return Err(ReadError::Eof);
+        }
+        M::from_bytes(&self.buffer[..len]).map_err(ReadError::Parse)
+    }
+}
+
+impl<M: Flat + ?Sized, W: MsgWrite + Unpin> MsgWriter<M, W> {
+    pub fn new(channel: W, max_msg_len: usize) -> Self {
+        Self {
+            channel,
+            buffer: Buffer::for_msg::<M>(max_msg_len),
+            _p: PhantomData,
+        }
+    }
+
+    pub fn alloc_message(&mut self) -> UninitWriteGuard<'_, M, W> {
+        UninitWriteGuard { writer: self }
+    }
+}
+
+impl<'a, M: Flat + ?Sized, W: MsgWrite + Unpin> UninitWriteGuard<'a, M, W> {
+    pub fn new_in_place(
+        self,
+        emplacer: impl Emplacer<M>,
+    ) -> Result<WriteGuard<'a, M, W>, flatty::Error> {
+        M::new_in_place(&mut self.writer.buffer, emplacer)?;
+        Ok(WriteGuard {
+            writer: self.writer,
+        })
+    }
+}
+
+impl<'a, M: Flat + ?Sized, W: MsgWrite + Unpin> Deref for WriteGuard<'a, M, W> {
+    type Target = M;
+    fn deref(&self) -> &M {
+        unsafe { M::from_bytes_unchecked(&self.writer.buffer) }
+    }
+}
+impl<'a, M: Flat + ?Sized, W: MsgWrite + Unpin> DerefMut for WriteGuard<'a, M, W> {
+    fn deref_mut(&mut self) -> &mut M {
+        unsafe { M::from_mut_bytes_unchecked(&mut self.writer.buffer) }
+    }
+}
+
+impl<'a, M: Flat + ?Sized, W: MsgWrite + Unpin> WriteGuard<'a, M, W> {
+    pub async fn write(self) -> Result<(), io::Error> {
+        let size = self.size();
+        let MsgWriter {
+            channel, buffer, ..
+        } = self.writer;
+        poll_fn(|cx| Pin::new(&mut *channel).poll_ready(cx)).await?;
+        Pin::new(&mut *channel).start_send(&buffer[..size])?;
+        poll_fn(|cx| Pin::new(&mut *channel).poll_flush(cx)).await
+    }
+}
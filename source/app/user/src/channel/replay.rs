@@ -0,0 +1,104 @@
+use super::{Channel, MsgRead, MsgWrite};
+use common::capture::{Direction, Records};
+use futures::{ready, Future};
+use std::{
+    collections::VecDeque,
+    io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    fs,
+    time::{sleep_until, Instant, Sleep},
+};
+
+/// Channel that feeds messages from MCU stored in capture with their original timing.
+///
+/// Messages sent to MCU are dropped.
+pub struct Replay {
+    /// Messages with their time since capture start.
+    records: VecDeque<(Duration, Vec<u8>)>,
+}
+
+pub struct ReplayRead {
+    records: VecDeque<(Duration, Vec<u8>)>,
+    start: Instant,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+pub struct ReplayWrite;
+
+impl Replay {
+    /// Load messages from MCU passed through channel `id`.
+    pub async fn open<P: AsRef<Path>>(path: P, id: u32) -> Result<Self, io::Error> {
+        let data = fs::read(path).await?;
+        let records = Records::new(&data)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not a capture"))?
+            .filter(|(header, _)| {
+                header.direction == Direction::McuToApp && header.channel as u32 == id
+            })
+            .map(|(header, msg)| (Duration::from_nanos(header.time_ns), msg.to_vec()))
+            .collect();
+        Ok(Self { records })
+    }
+}
+
+impl Channel for Replay {
+    type Read = ReplayRead;
+    type Write = ReplayWrite;
+    fn split(self) -> (ReplayRead, ReplayWrite) {
+        (
+            ReplayRead {
+                records: self.records,
+                start: Instant::now(),
+                sleep: None,
+            },
+            ReplayWrite,
+        )
+    }
+}
+
+impl MsgRead for ReplayRead {
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let time = match this.records.front() {
+            Some((time, _)) => *time,
+            // Capture is over, keep channel open to preserve the last state.
+            None => return Poll::Pending,
+        };
+        let start = this.start;
+        let sleep = this
+            .sleep
+            .get_or_insert_with(|| Box::pin(sleep_until(start + time)));
+        ready!(sleep.as_mut().poll(cx));
+        this.sleep = None;
+
+        let (_, msg) = this.records.pop_front().unwrap();
+        if msg.len() > buf.len() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Message is too long",
+            )));
+        }
+        buf[..msg.len()].copy_from_slice(&msg);
+        Poll::Ready(Ok(msg.len()))
+    }
+}
+
+impl MsgWrite for ReplayWrite {
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn start_send(self: Pin<&mut Self>, _msg: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -1,6 +1,8 @@
-use super::Channel;
+use super::{Channel, MsgRead, MsgWrite};
+use common::config;
 use futures::ready;
 use std::{
+    env,
     fs::{File, OpenOptions},
     io::{self, Read, Write},
     os::{
@@ -8,14 +10,16 @@ use std::{
         raw::c_void,
         unix::fs::OpenOptionsExt,
     },
-    path::Path,
+    path::{Path, PathBuf},
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
 };
-use termios::Termios;
-use tokio::io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf};
+use tokio::io::unix::AsyncFd;
 
+/// RPMSG endpoint device provided by `rpmsg_char` driver.
+///
+/// Unlike RPMSG TTY it preserves message boundaries.
 pub struct Rpmsg {
     fd: RawFd,
 }
@@ -32,6 +36,8 @@ pub struct AsyncReader {
 
 pub struct AsyncWriter {
     raw: Arc<AsyncFd<Rpmsg>>,
+    /// Message that is started but not sent yet.
+    pending: Vec<u8>,
 }
 
 pub struct Reader {
@@ -48,27 +54,57 @@ impl Rpmsg {
             let file = OpenOptions::new()
                 .read(true)
                 .write(true)
-                .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+                .custom_flags(libc::O_NONBLOCK)
                 .open(path)?;
             file.into_raw_fd()
         };
-        {
-            let mut tty = Termios::from_fd(fd)?;
-            termios::cfmakeraw(&mut tty);
-            termios::tcsetattr(fd, termios::TCSAFLUSH, &tty)?;
-        }
-
         Ok(Rpmsg { fd })
     }
 }
 
+/// Path of RPMSG endpoint device for channel `id`.
+///
+/// Taken from channel mapping if it contains `id`, otherwise channel id is appended to prefix.
+/// Both mapping and prefix may be set by environment variables.
+pub fn device_path(id: u32) -> PathBuf {
+    if let Ok(mapping) = env::var(config::RPMSG_CHANNELS_ENV) {
+        if let Some(path) = mapped_path(&mapping, id) {
+            return path;
+        }
+    }
+    let prefix = env::var(config::RPMSG_DEVICE_ENV).unwrap_or_else(|_| config::RPMSG_DEVICE.into());
+    format!("{}{}", prefix, id).into()
+}
+
+fn mapped_path(mapping: &str, id: u32) -> Option<PathBuf> {
+    mapping
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (key, path) = entry.split_once('=').expect("Bad RPMSG channel mapping");
+            (
+                key.trim().parse::<u32>().expect("Bad RPMSG channel id"),
+                path.trim(),
+            )
+        })
+        .find(|(key, _)| *key == id)
+        .map(|(_, path)| path.into())
+}
+
 impl Channel for Rpmsg {
     type Read = AsyncReader;
     type Write = AsyncWriter;
 
     fn split(self) -> (AsyncReader, AsyncWriter) {
         let raw = Arc::new(AsyncFd::new(self).unwrap());
-        (AsyncReader { raw: raw.clone() }, AsyncWriter { raw })
+        (
+            AsyncReader { raw: raw.clone() },
+            AsyncWriter {
+                raw,
+                pending: Vec::new(),
+            },
+        )
     }
 }
 
@@ -106,45 +142,56 @@ impl Drop for Rpmsg {
     }
 }
 
-impl AsyncRead for AsyncReader {
-    fn poll_read(
+/// Each read of endpoint device returns a single RPMSG message.
+impl MsgRead for AsyncReader {
+    fn poll_recv(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<io::Result<()>> {
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
         loop {
             let mut guard = ready!(self.raw.poll_read_ready(cx))?;
-            let dst = buf.initialize_unfilled();
-            match guard.try_io(|raw| raw.get_ref().read(dst)) {
-                Ok(Ok(n)) => {
-                    buf.advance(n);
-                    break Poll::Ready(Ok(()));
-                }
-                Ok(Err(e)) => break Poll::Ready(Err(e)),
+            match guard.try_io(|raw| raw.get_ref().read(buf)) {
+                Ok(res) => return Poll::Ready(res),
                 Err(_) => continue,
             }
         }
     }
 }
 
-impl AsyncWrite for AsyncWriter {
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<io::Result<usize>> {
-        loop {
-            let mut guard = ready!(self.raw.poll_write_ready(cx))?;
-            match guard.try_io(|raw| raw.get_ref().write(buf)) {
-                Ok(r) => break Poll::Ready(r),
-                Err(_) => continue,
-            }
-        }
+/// Each write of the whole message sends it as a single RPMSG message, so partial writes are errors.
+impl MsgWrite for AsyncWriter {
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
     }
-    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Poll::Ready(Ok(()))
+    fn start_send(self: Pin<&mut Self>, msg: &[u8]) -> io::Result<()> {
+        let this = self.get_mut();
+        if !this.pending.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Previous message is not sent yet",
+            ));
+        }
+        this.pending.extend_from_slice(msg);
+        Ok(())
     }
-    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while !this.pending.is_empty() {
+            let mut guard = ready!(this.raw.poll_write_ready(cx))?;
+            let msg = &this.pending;
+            let res = match guard.try_io(|raw| raw.get_ref().write(msg)) {
+                Ok(Ok(n)) if n == msg.len() => Ok(()),
+                Ok(Ok(_)) => Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "Message was partially written",
+                )),
+                Ok(Err(e)) => Err(e),
+                Err(_) => continue,
+            };
+            this.pending.clear();
+            return Poll::Ready(res);
+        }
         Poll::Ready(Ok(()))
     }
 }
@@ -6,17 +6,41 @@ use ferrite::TypedVariable as Variable;
 use ringbuf::traits::*;
 use std::{
     iter::ExactSizeIterator,
-    sync::{atomic::Ordering, Arc},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
+/// High watermark of AI buffer occupancy since the last reset.
+#[derive(Clone, Default)]
+pub struct MaxFill(Arc<AtomicUsize>);
+
+impl MaxFill {
+    fn update(&self, len: usize) {
+        self.0.fetch_max(len, Ordering::Relaxed);
+    }
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+    pub fn reset(&self) {
+        self.0.store(0, Ordering::Relaxed);
+    }
+}
+
 pub struct Ai {
     input: <AsyncHeapRb<Point> as Split>::Cons,
     output: Variable<[f64]>,
+    max_fill: MaxFill,
+    /// Published on each waveform.
+    max_fill_output: Variable<i32>,
 }
 
 pub struct AiHandle {
     buffer: <AsyncHeapRb<Point> as Split>::Prod,
     last_point: Arc<AtomicUv>,
+    /// Updated after each push.
+    max_fill: MaxFill,
 }
 
 impl Ai {
@@ -24,24 +48,32 @@ impl Ai {
         let buffer = AsyncHeapRb::<Point>::new(2 * epics.waveform.max_len());
         let (producer, consumer) = buffer.split();
         let last = Arc::new(AtomicUv::default());
+        let max_fill = MaxFill::default();
         (
             Self {
                 input: consumer,
                 output: epics.waveform,
+                max_fill: max_fill.clone(),
+                max_fill_output: epics.max_fill,
             },
             AiHandle {
                 buffer: producer,
                 last_point: last,
+                max_fill,
             },
         )
     }
 
+    pub fn max_fill(&self) -> MaxFill {
+        self.max_fill.clone()
+    }
+
     pub async fn run(mut self) -> Result<(), Error> {
         let max_len = self.output.max_len();
         loop {
             self.input.wait_occupied(max_len).await;
             if self.input.is_closed() {
-                break Err(Error::Disconnected);
+                break Err(Error::ChannelClosed);
             }
             assert!(self.input.occupied_len() >= max_len);
             self.output
@@ -53,6 +85,8 @@ impl Ai {
                     PointOpt::Sep => None,
                 }))
                 .await;
+            let max_fill = self.max_fill.get().try_into().unwrap_or(i32::MAX);
+            self.max_fill_output.request().await.write(max_fill).await;
         }
     }
 }
@@ -71,6 +105,7 @@ impl AiHandle {
             })),
             len
         );
+        self.max_fill.update(self.buffer.occupied_len());
         if len > 0 {
             self.last_point.store(last, Ordering::Release);
         }
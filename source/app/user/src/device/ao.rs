@@ -22,6 +22,7 @@ impl Ao {
         ready.store(1);
         let cycle = AtomicVariable::new(epics.next_cycle);
         let add = GenericSubscriber::new(AtomicVariable::new(epics.add));
+        let readback_enable = GenericSubscriber::new(AtomicVariable::new(epics.readback_enable));
 
         (
             Self {
@@ -34,6 +35,7 @@ impl Ao {
             AoHandle {
                 buffer: read_buffer.into_iter(AoModifier { ready, cycle }),
                 add: Box::pin(add.into_stream().map(volt_to_uv_saturating)),
+                readback_enable: Box::pin(readback_enable.into_stream().map(|value| value != 0)),
             },
         )
     }
@@ -48,6 +50,7 @@ pub struct AoHandle {
     pub buffer: double_vec::ReadIterator<Uv, AoModifier>,
     // TODO: Remove `Box` when `impl Trait` stabilized.
     pub add: Pin<Box<dyn Stream<Item = Uv> + Send>>,
+    pub readback_enable: Pin<Box<dyn Stream<Item = bool> + Send>>,
 }
 
 pub struct AoModifier {
@@ -1,23 +1,57 @@
 use std::pin::Pin;
 
+use super::ai::MaxFill;
 use crate::epics;
-use futures::{Stream, StreamExt};
+use common::{config::AI_COUNT, protocol::stats_reset};
+use ferrite::TypedVariable as Variable;
+use futures::{stream, Stream, StreamExt};
 
 pub enum Debug {}
 
 pub struct DebugHandle {
-    pub stats_reset: Pin<Box<dyn Stream<Item = ()> + Send>>,
+    /// Masks of MCU statistics groups to reset, see `stats_reset`.
+    ///
+    /// IOC-side statistics of the same groups are reset when mask is yielded.
+    pub stats_reset: Pin<Box<dyn Stream<Item = u32> + Send>>,
+}
+
+/// Yields `mask` each time non-zero value is written to `var`.
+fn reset_on_write(var: Variable<u16>, mask: u32) -> impl Stream<Item = u32> + Send {
+    var.into_stream().filter_map(move |x| async move {
+        if x != 0 {
+            Some(mask)
+        } else {
+            None
+        }
+    })
 }
 
 impl Debug {
     #[allow(clippy::new_ret_no_self)]
-    pub fn new(epics: epics::Debug) -> DebugHandle {
+    pub fn new(
+        epics: epics::Debug,
+        ao_readback_fill: MaxFill,
+        ai_fills: [MaxFill; AI_COUNT],
+    ) -> DebugHandle {
+        let mut resets = vec![
+            reset_on_write(epics.reset_stats, stats_reset::ALL).boxed(),
+            reset_on_write(epics.reset_stats_global, stats_reset::GLOBAL).boxed(),
+            reset_on_write(epics.reset_stats_ao, stats_reset::AO).boxed(),
+            reset_on_write(epics.reset_stats_crc_errors, stats_reset::CRC_ERRORS).boxed(),
+            reset_on_write(epics.reset_stats_ioc_drops, stats_reset::IOC_DROPS).boxed(),
+        ];
+        for (index, var) in epics.reset_stats_ais.into_iter().enumerate() {
+            resets.push(reset_on_write(var, stats_reset::ai(index)).boxed());
+        }
         DebugHandle {
-            stats_reset: Box::pin(epics.reset_stats.into_stream().filter_map(|x| async move {
-                if x != 0 {
-                    Some(())
-                } else {
-                    None
+            stats_reset: Box::pin(stream::select_all(resets).inspect(move |&mask| {
+                if mask & stats_reset::AO != 0 {
+                    ao_readback_fill.reset();
+                }
+                for (index, fill) in ai_fills.iter().enumerate() {
+                    if mask & stats_reset::ai(index) != 0 {
+                        fill.reset();
+                    }
                 }
             })),
         }
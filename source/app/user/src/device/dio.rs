@@ -32,7 +32,7 @@ impl Do {
             let input = u8::try_from(self.variable.wait().await.read().await).unwrap();
             let value = DoValue::try_from(input).unwrap();
             if self.channel.send(value).await.is_err() {
-                break Err(Error::Disconnected);
+                break Err(Error::ChannelClosed);
             }
         }
     }
@@ -60,7 +60,7 @@ impl Di {
         loop {
             let value = match self.channel.next().await {
                 Some(value) => value,
-                None => break Err(Error::Disconnected),
+                None => break Err(Error::ChannelClosed),
             };
             self.variable
                 .request()
@@ -1,126 +1,362 @@
 use super::{
     ai::AiHandle,
-    ao::AoHandle,
+    ao::{AoHandle, AoModifier},
     debug::DebugHandle,
     dio::{DiHandle, DoHandle},
+    health::HealthHandle,
+    loop_stats::{LoopHandle, LoopStats},
+    rates::{Counters, RatesHandle},
+    skifio::{SkifioHandle, SkifioState},
+    supervisor::until_stop,
+    tasks::{TaskStats, TasksHandle},
     Error,
 };
-use crate::channel::Channel;
+use crate::{
+    channel::{
+        msg::{MsgReader, MsgWriter, ReadError},
+        Channel, MsgRead, MsgWrite,
+    },
+    utils::double_vec::ReadIterator,
+};
 use async_atomic::{Atomic as AsyncAtomic, Subscriber};
-use async_compat::Compat;
 use common::{
     config::{self, AI_COUNT},
     protocol::{self as proto, AppMsg, McuMsg, McuMsgRef},
-    values::Point,
+    values::{Do, Point, Uv},
+};
+use flatty::{flat_vec, portable::Bool, prelude::*, Emplacer};
+use futures::{join, try_join, SinkExt, Stream, StreamExt};
+use std::{io, pin::Pin, sync::Arc};
+use tokio::{
+    sync::{watch, Mutex},
+    time::sleep,
 };
-use flatty::{flat_vec, prelude::*, Emplacer};
-use flatty_io::{AsyncReader as MsgReader, AsyncWriter as MsgWriter, ReadError};
-use futures::{future::try_join_all, join, AsyncWrite, FutureExt, SinkExt, StreamExt};
-use std::{io, sync::Arc};
-use tokio::{spawn, sync::Mutex, time::sleep};
-
-pub struct Dispatcher<C: Channel> {
-    writer: Writer<C>,
-    reader: Reader<C>,
-}
 
-struct Writer<C: Channel> {
-    channel: Mutex<MsgWriter<AppMsg, Compat<C::Write>>>,
+/// Device handles that outlive a single connection to MCU.
+pub struct Handles {
     ao: AoHandle,
-    ao_write_count: Subscriber<usize>,
+    ao_readback: AiHandle,
+    ais: [AiHandle; AI_COUNT],
+    di: DiHandle,
     do_: DoHandle,
+    skifio: SkifioHandle,
+    loop_: LoopHandle,
+    tasks: TasksHandle,
+    rates: RatesHandle,
     debug: DebugHandle,
+
+    /// No connection has been established yet.
+    initial: bool,
+    /// Last DO value, resent on reconnection.
+    last_do: Option<Do>,
+    /// Last AO correction, resent on reconnection.
+    last_ao_add: Option<Uv>,
+    /// Last AO readback state, resent on reconnection.
+    last_ao_readback: Option<bool>,
 }
 
-struct Reader<C: Channel> {
-    channel: MsgReader<McuMsg, Compat<C::Read>>,
-    ais: [AiHandle; AI_COUNT],
+pub struct Dispatcher<'a, C: Channel> {
+    writer: Writer<'a, C>,
+    control_reader: ControlReader<'a, C>,
+    data_reader: DataReader<'a, C>,
+}
+
+struct Writer<'a, C: Channel> {
+    control: MsgWriter<AppMsg, C::Write>,
+    data: MsgWriter<AppMsg, C::Write>,
+    ao: &'a mut AoHandle,
+    ao_write_count: Subscriber<usize>,
+    do_: &'a mut DoHandle,
+    debug: &'a mut DebugHandle,
+    initial: bool,
+    last_do: &'a mut Option<Do>,
+    last_ao_add: &'a mut Option<Uv>,
+    last_ao_readback: &'a mut Option<bool>,
+    health: HealthHandle,
+    stop: watch::Receiver<bool>,
+}
+
+/// Receives control and status messages.
+struct ControlReader<'a, C: Channel> {
+    channel: MsgReader<McuMsg, C::Read>,
     ao_write_count: Arc<AsyncAtomic<usize>>,
-    di: DiHandle,
+    di: &'a mut DiHandle,
+    skifio: &'a mut SkifioHandle,
+    loop_: &'a mut LoopHandle,
+    tasks: &'a mut TasksHandle,
+    rates: &'a mut RatesHandle,
+    /// IOC-side DO value at connection, MCU state is checked against it.
+    expected_do: Option<Do>,
+    /// IOC-side AO correction at connection, MCU state is checked against it.
+    expected_ao_add: Option<Uv>,
+    health: HealthHandle,
 }
 
-impl<C: Channel> Dispatcher<C> {
-    pub async fn new(
-        channel: C,
+/// Receives bulk AI and AO readback data.
+struct DataReader<'a, C: Channel> {
+    channel: MsgReader<McuMsg, C::Read>,
+    ao_readback: &'a mut AiHandle,
+    ais: &'a mut [AiHandle; AI_COUNT],
+    health: HealthHandle,
+}
+
+impl Handles {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
         ao: AoHandle,
+        ao_readback: AiHandle,
         ais: [AiHandle; AI_COUNT],
         di: DiHandle,
         do_: DoHandle,
+        skifio: SkifioHandle,
+        loop_: LoopHandle,
+        tasks: TasksHandle,
+        rates: RatesHandle,
         debug: DebugHandle,
     ) -> Self {
-        let (r, w) = channel.split();
-        let (r, w) = (Compat::new(r), Compat::new(w));
-        let reader = MsgReader::<McuMsg, _>::new(r, config::MAX_MCU_MSG_LEN);
-        let writer = Mutex::new(MsgWriter::<AppMsg, _>::new(w, config::MAX_APP_MSG_LEN));
+        Self {
+            ao,
+            ao_readback,
+            ais,
+            di,
+            do_,
+            skifio,
+            loop_,
+            tasks,
+            rates,
+            debug,
+            initial: true,
+            last_do: None,
+            last_ao_add: None,
+            last_ao_readback: None,
+        }
+    }
+}
+
+impl<'a, C: Channel> Dispatcher<'a, C> {
+    pub fn new(
+        control: C,
+        data: C,
+        handles: &'a mut Handles,
+        health: HealthHandle,
+        stop: watch::Receiver<bool>,
+    ) -> Self {
+        let (control_r, control_w) = control.split();
+        let (data_r, data_w) = data.split();
         let ao_write_count = AsyncAtomic::new(0).subscribe();
+        let initial = handles.initial;
+        handles.initial = false;
+        let Handles {
+            ao,
+            ao_readback,
+            ais,
+            di,
+            do_,
+            skifio,
+            loop_,
+            tasks,
+            rates,
+            debug,
+            last_do,
+            last_ao_add,
+            last_ao_readback,
+            ..
+        } = handles;
         Self {
-            reader: Reader {
-                channel: reader,
-                ais,
+            control_reader: ControlReader {
+                channel: MsgReader::new(control_r, config::MAX_MCU_MSG_LEN),
                 ao_write_count: ao_write_count.clone(),
                 di,
+                skifio,
+                loop_,
+                tasks,
+                rates,
+                expected_do: *last_do,
+                expected_ao_add: *last_ao_add,
+                health: health.clone(),
+            },
+            data_reader: DataReader {
+                channel: MsgReader::new(data_r, config::MAX_MCU_MSG_LEN),
+                ao_readback,
+                ais,
+                health: health.clone(),
             },
             writer: Writer {
-                channel: writer,
+                control: MsgWriter::new(control_w, config::MAX_APP_MSG_LEN),
+                data: MsgWriter::new(data_w, config::MAX_APP_MSG_LEN),
                 ao,
                 ao_write_count,
                 do_,
                 debug,
+                initial,
+                last_do,
+                last_ao_add,
+                last_ao_readback,
+                health,
+                stop,
             },
         }
     }
     pub async fn run(self) -> Result<(), Error> {
-        try_join_all([
-            spawn(self.reader.run()).map(Result::unwrap),
-            spawn(self.writer.run()).map(Result::unwrap),
-        ])
-        .await
+        try_join!(
+            self.control_reader.run(),
+            self.data_reader.run(),
+            self.writer.run()
+        )
         .map(|_| ())
     }
 }
 
-impl<C: Channel> Reader<C> {
-    async fn run(mut self) -> Result<(), Error> {
+async fn read_message<'b, R: MsgRead + Unpin>(
+    channel: &'b mut MsgReader<McuMsg, R>,
+    health: &HealthHandle,
+) -> Result<&'b McuMsg, Error> {
+    let msg = match channel.read_message().await {
+        Ok(msg) => msg,
+        Err(ReadError::Eof) => return Err(Error::Disconnected),
+        Err(ReadError::Io(err)) => return Err(err.into()),
+        Err(ReadError::Parse(err)) => return Err(Error::Parse(err)),
+    };
+    health.report_message(&msg.as_ref());
+    Ok(msg)
+}
+
+impl<'a, C: Channel> ControlReader<'a, C> {
+    async fn run(self) -> Result<(), Error> {
         let mut channel = self.channel;
-        let mut ais = self.ais;
         loop {
-            let msg = match channel.read_message().await {
-                Err(ReadError::Eof) => break Err(Error::Disconnected),
-                Err(ReadError::Io(err)) => {
-                    if err.kind() == io::ErrorKind::ConnectionReset {
-                        break Err(Error::Disconnected);
-                    } else {
-                        panic!("I/O error: {}", err);
-                    }
-                }
-                other => other.unwrap(),
-            };
+            let msg = read_message(&mut channel, &self.health).await?;
             match msg.as_ref() {
-                McuMsgRef::DiUpdate { value } => self.di.send(*value).await.unwrap(),
+                McuMsgRef::DiUpdate { value } => self
+                    .di
+                    .send(*value)
+                    .await
+                    .map_err(|_| Error::ChannelClosed)?,
                 McuMsgRef::AoRequest { count } => {
                     self.ao_write_count.fetch_add(*count as usize);
                 }
-                McuMsgRef::AiData { points } => {
-                    for (index, ai) in ais.iter_mut().enumerate() {
-                        ai.push_iter(points.iter().map(|a| a[index])).await;
-                    }
-                }
                 McuMsgRef::Error { code, message } => {
-                    panic!(
-                        "Error {}: {}",
-                        code,
-                        String::from_utf8_lossy(message.as_slice())
-                    )
+                    break Err(Error::Mcu {
+                        code: *code,
+                        message: String::from_utf8_lossy(message.as_slice()).into_owned(),
+                    })
                 }
                 McuMsgRef::Debug { message } => {
                     println!("Debug: {}", String::from_utf8_lossy(message.as_slice()))
                 }
+                McuMsgRef::KeepAliveAck { seq } => self.health.report_keep_alive_ack(*seq),
+                McuMsgRef::StateSync {
+                    di,
+                    do_,
+                    ao_enabled,
+                    ao_add,
+                } => {
+                    log::info!(
+                        "MCU state: di={:?}, do={:?}, ao_enabled={}, ao_add={}",
+                        di,
+                        do_,
+                        ao_enabled.to_native(),
+                        ao_add
+                    );
+                    // IOC-side values are resent after connection, so mismatch is repaired.
+                    if let Some(expected) = self.expected_do.filter(|value| value != do_) {
+                        log::warn!(
+                            "MCU DO {:?} differs from IOC {:?}, restoring",
+                            do_,
+                            expected
+                        );
+                    }
+                    if let Some(expected) = self.expected_ao_add.filter(|value| value != ao_add) {
+                        log::warn!(
+                            "MCU AO correction {} differs from IOC {}, restoring",
+                            ao_add,
+                            expected
+                        );
+                    }
+                    self.di.send(*di).await.map_err(|_| Error::ChannelClosed)?;
+                }
+                McuMsgRef::SkifioState { temp, status } => self
+                    .skifio
+                    .send(SkifioState {
+                        temp: *temp,
+                        status: *status,
+                    })
+                    .await
+                    .map_err(|_| Error::ChannelClosed)?,
+                McuMsgRef::LoopStats {
+                    cycle,
+                    wait_ready,
+                    cycle_max,
+                    wait_ready_max,
+                } => self
+                    .loop_
+                    .send(LoopStats {
+                        cycle: *cycle,
+                        wait_ready: *wait_ready,
+                        cycle_max: *cycle_max,
+                        wait_ready_max: *wait_ready_max,
+                    })
+                    .await
+                    .map_err(|_| Error::ChannelClosed)?,
+                McuMsgRef::TaskStats {
+                    run_time_freq,
+                    tasks,
+                } => self
+                    .tasks
+                    .send(TaskStats {
+                        run_time_freq: *run_time_freq,
+                        tasks: tasks.as_slice().to_vec(),
+                    })
+                    .await
+                    .map_err(|_| Error::ChannelClosed)?,
+                McuMsgRef::StatsCounters {
+                    sync,
+                    samples,
+                    crc_errors,
+                    ao_lost,
+                    time_us,
+                } => self
+                    .rates
+                    .send(Counters {
+                        sync: *sync,
+                        samples: *samples,
+                        crc_errors: *crc_errors,
+                        ao_lost: *ao_lost,
+                        time_us: *time_us,
+                    })
+                    .await
+                    .map_err(|_| Error::ChannelClosed)?,
+                McuMsgRef::AiData { .. } | McuMsgRef::AoReadback { .. } => {
+                    log::warn!("Unexpected bulk data on control channel")
+                }
+            }
+        }
+    }
+}
+
+impl<'a, C: Channel> DataReader<'a, C> {
+    async fn run(self) -> Result<(), Error> {
+        let mut channel = self.channel;
+        loop {
+            let msg = read_message(&mut channel, &self.health).await?;
+            match msg.as_ref() {
+                McuMsgRef::AiData { points } => {
+                    for (index, ai) in self.ais.iter_mut().enumerate() {
+                        ai.push_iter(points.iter().map(|a| a[index])).await;
+                    }
+                }
+                McuMsgRef::AoReadback { points } => {
+                    self.ao_readback.push_iter(points.iter().copied()).await;
+                }
+                _ => log::warn!("Unexpected message on data channel"),
             }
         }
     }
 }
 
-async fn send_message<M: Flat + ?Sized, W: AsyncWrite + Unpin, E: Emplacer<M>>(
+type SharedWriter<W> = Mutex<MsgWriter<AppMsg, W>>;
+
+async fn send_message<M: Flat + ?Sized, W: MsgWrite + Unpin, E: Emplacer<M>>(
     channel: &Mutex<MsgWriter<M, W>>,
     emplacer: E,
 ) -> Result<(), io::Error> {
@@ -134,91 +370,175 @@ async fn send_message<M: Flat + ?Sized, W: AsyncWrite + Unpin, E: Emplacer<M>>(
         .await
 }
 
-impl<C: Channel> Writer<C> {
-    async fn run(mut self) -> Result<(), Error> {
-        let channel = Arc::new(self.channel);
-        let res: Result<Vec<()>, io::Error> = try_join_all([
-            spawn({
-                let channel = channel.clone();
-                async move {
-                    loop {
-                        send_message(&channel, proto::AppMsgInitKeepAlive).await?;
-                        sleep(config::KEEP_ALIVE_PERIOD).await;
-                    }
-                }
-            })
-            .map(Result::unwrap),
-            spawn({
-                let channel = channel.clone();
-                async move {
-                    loop {
-                        send_message(&channel, proto::AppMsgInitStatsReset).await?;
-                        self.debug.stats_reset.next().await;
-                    }
-                }
-            })
-            .map(Result::unwrap),
-            spawn({
-                let channel = channel.clone();
-                async move {
-                    loop {
-                        let value = self.do_.next().await.unwrap();
-                        send_message(&channel, proto::AppMsgInitDoUpdate { value }).await?;
-                    }
-                }
-            })
-            .map(Result::unwrap),
-            spawn({
-                let channel = channel.clone();
-                async move {
-                    loop {
-                        let value = self.ao.add.next().await.unwrap();
-                        send_message(&channel, proto::AppMsgInitAoAdd { value }).await?;
-                    }
-                }
+impl<'a, C: Channel> Writer<'a, C> {
+    async fn run(self) -> Result<(), Error> {
+        let Self {
+            control,
+            data,
+            ao,
+            ao_write_count,
+            do_,
+            debug,
+            initial,
+            last_do,
+            last_ao_add,
+            last_ao_readback,
+            health,
+            mut stop,
+        } = self;
+        let channel = Mutex::new(control);
+        let data = Mutex::new(data);
+        let res = async {
+            send_message(&data, proto::AppMsgInitHello).await?;
+            // Push IOC-side state to MCU.
+            // DO, AO correction and AO readback state are resent in their loops.
+            send_message(
+                &channel,
+                proto::AppMsgInitAoState {
+                    enable: Bool::from_native(true),
+                },
+            )
+            .await?;
+            let run = async {
+                try_join!(
+                    send_keep_alive(&channel, &health),
+                    send_stats_reset(&channel, debug, initial),
+                    send_do(&channel, do_, last_do),
+                    send_ao_add(&channel, &mut ao.add, last_ao_add),
+                    send_ao_readback(&channel, &mut ao.readback_enable, last_ao_readback),
+                    send_ao_data(&data, &mut ao.buffer, ao_write_count),
+                )
+            };
+            match until_stop(&mut stop, run).await {
+                Some(res) => res.map(|_| ()),
+                // Message being sent when stop was requested is completed by channel
+                // before goodbye, so stream stays consistent.
+                None => Ok(send_message(&channel, proto::AppMsgInitGoodbye).await?),
+            }
+        }
+        .await;
+        // Sending loops never finish successfully, so we get `Ok` only after saying goodbye.
+        res.and(Err(Error::Stopped))
+    }
+}
+
+async fn send_keep_alive<W: MsgWrite + Unpin>(
+    channel: &SharedWriter<W>,
+    health: &HealthHandle,
+) -> Result<(), Error> {
+    loop {
+        let seq = health.start_keep_alive();
+        send_message(channel, proto::AppMsgInitKeepAlive { seq }).await?;
+        sleep(config::KEEP_ALIVE_PERIOD).await;
+    }
+}
+
+async fn send_stats_reset<W: MsgWrite + Unpin>(
+    channel: &SharedWriter<W>,
+    debug: &mut DebugHandle,
+    initial: bool,
+) -> Result<(), Error> {
+    // Reset all statistics only on the first connection to keep IOC drop counters.
+    let mut mask = if initial {
+        proto::stats_reset::ALL
+    } else {
+        debug.stats_reset.next().await.ok_or(Error::ChannelClosed)?
+    };
+    loop {
+        send_message(channel, proto::AppMsgInitStatsReset { mask }).await?;
+        mask = debug.stats_reset.next().await.ok_or(Error::ChannelClosed)?;
+    }
+}
+
+async fn send_do<W: MsgWrite + Unpin>(
+    channel: &SharedWriter<W>,
+    do_: &mut DoHandle,
+    last: &mut Option<Do>,
+) -> Result<(), Error> {
+    if let Some(value) = *last {
+        send_message(channel, proto::AppMsgInitDoUpdate { value }).await?;
+    }
+    loop {
+        let value = do_.next().await.ok_or(Error::ChannelClosed)?;
+        *last = Some(value);
+        send_message(channel, proto::AppMsgInitDoUpdate { value }).await?;
+    }
+}
+
+async fn send_ao_add<W: MsgWrite + Unpin>(
+    channel: &SharedWriter<W>,
+    add: &mut Pin<Box<dyn Stream<Item = Uv> + Send>>,
+    last: &mut Option<Uv>,
+) -> Result<(), Error> {
+    if let Some(value) = *last {
+        send_message(channel, proto::AppMsgInitAoAdd { value }).await?;
+    }
+    loop {
+        let value = add.next().await.ok_or(Error::ChannelClosed)?;
+        *last = Some(value);
+        send_message(channel, proto::AppMsgInitAoAdd { value }).await?;
+    }
+}
+
+async fn send_ao_readback<W: MsgWrite + Unpin>(
+    channel: &SharedWriter<W>,
+    enable: &mut Pin<Box<dyn Stream<Item = bool> + Send>>,
+    last: &mut Option<bool>,
+) -> Result<(), Error> {
+    if let Some(value) = *last {
+        send_message(
+            channel,
+            proto::AppMsgInitAoReadbackState {
+                enable: Bool::from_native(value),
+            },
+        )
+        .await?;
+    }
+    loop {
+        let value = enable.next().await.ok_or(Error::ChannelClosed)?;
+        *last = Some(value);
+        send_message(
+            channel,
+            proto::AppMsgInitAoReadbackState {
+                enable: Bool::from_native(value),
+            },
+        )
+        .await?;
+    }
+}
+
+async fn send_ao_data<W: MsgWrite + Unpin>(
+    channel: &SharedWriter<W>,
+    iter: &mut ReadIterator<Uv, AoModifier>,
+    mut write_count: Subscriber<usize>,
+) -> Result<(), Error> {
+    loop {
+        join!(write_count.wait(|x| x >= 1), iter.wait_ready());
+        let mut guard = channel.lock().await;
+        let mut msg = guard
+            .alloc_message()
+            .new_in_place(proto::AppMsgInitAoData {
+                points: flat_vec![],
             })
-            .map(Result::unwrap),
-            spawn(async move {
-                let mut iter = self.ao.buffer;
-                loop {
-                    join!(self.ao_write_count.wait(|x| x >= 1), iter.wait_ready());
-                    let mut guard = channel.lock().await;
-                    let mut msg = guard
-                        .alloc_message()
-                        .new_in_place(proto::AppMsgInitAoData {
-                            points: flat_vec![],
-                        })
-                        .unwrap();
-                    let will_send = if let proto::AppMsgMut::AoData { points } = msg.as_mut() {
-                        let mut count = self.ao_write_count.swap(0);
-                        while count > 0 && !points.is_full() {
-                            match iter.next() {
-                                Some(value) => {
-                                    points.push(Point::from_uv(value)).unwrap();
-                                    count -= 1;
-                                }
-                                None => break,
-                            }
-                        }
-                        self.ao_write_count.fetch_add(count);
-                        !points.is_empty()
-                    } else {
-                        unreachable!();
-                    };
-                    if will_send {
-                        msg.write().await?;
+            .unwrap();
+        let will_send = if let proto::AppMsgMut::AoData { points } = msg.as_mut() {
+            let mut count = write_count.swap(0);
+            while count > 0 && !points.is_full() {
+                match iter.next() {
+                    Some(value) => {
+                        points.push(Point::from_uv(value)).unwrap();
+                        count -= 1;
                     }
+                    None => break,
                 }
-            })
-            .map(Result::unwrap),
-        ])
-        .await;
-        match res {
-            Ok(_) => Ok(()),
-            Err(err) => match err.kind() {
-                io::ErrorKind::BrokenPipe => Err(Error::Disconnected),
-                other => panic!("I/O Error: {}", other),
-            },
+            }
+            write_count.fetch_add(count);
+            !points.is_empty()
+        } else {
+            unreachable!();
+        };
+        if will_send {
+            msg.write().await?;
         }
     }
 }
@@ -0,0 +1,177 @@
+use super::Error;
+use crate::{epics, utils::stat::TimeStat};
+use common::protocol::{McuMsgRef, MCU_MSG_NAMES};
+use ferrite::{atomic::AtomicVariable, TypedVariable as Variable};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+/// Period of updating health PVs.
+const UPDATE_PERIOD: Duration = Duration::from_millis(500);
+
+const MCU_MSG_COUNT: usize = MCU_MSG_NAMES.len();
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChannelState {
+    Disconnected = 0,
+    Connecting = 1,
+    Connected = 2,
+}
+
+/// Periodically publishes connection health counters.
+pub struct Health {
+    counters: HealthHandle,
+    last_message_age: Variable<f64>,
+    reconnect_count: Variable<i32>,
+    message_counts: [Variable<i32>; MCU_MSG_COUNT],
+    rtt_min: Variable<f64>,
+    rtt_avg: Variable<f64>,
+    rtt_max: Variable<f64>,
+}
+
+pub type HealthHandle = Arc<HealthCounters>;
+
+pub struct HealthCounters {
+    connected: Arc<AtomicVariable<u16>>,
+    channel_state: Arc<AtomicVariable<u16>>,
+    /// Number of times channel was re-established after being lost.
+    reconnect_count: AtomicU64,
+    /// Time of the last message received from MCU.
+    last_message: Mutex<Instant>,
+    /// Number of received messages of each `McuMsg` variant.
+    message_counts: [AtomicU64; MCU_MSG_COUNT],
+    keep_alive: Mutex<KeepAlive>,
+}
+
+struct KeepAlive {
+    /// Sequence number of the next keep-alive message.
+    next_seq: u32,
+    /// Sequence number and send time of the last unacknowledged keep-alive message.
+    pending: Option<(u32, Instant)>,
+    /// Round-trip time since last update.
+    rtt: TimeStat,
+}
+
+impl Health {
+    pub fn new(epics: epics::Health) -> (Self, HealthHandle) {
+        let counters = Arc::new(HealthCounters {
+            connected: AtomicVariable::new(epics.connected),
+            channel_state: AtomicVariable::new(epics.channel_state),
+            reconnect_count: AtomicU64::new(0),
+            last_message: Mutex::new(Instant::now()),
+            message_counts: Default::default(),
+            keep_alive: Mutex::new(KeepAlive::new()),
+        });
+        counters.set_state(ChannelState::Disconnected);
+        (
+            Self {
+                counters: counters.clone(),
+                last_message_age: epics.last_message_age,
+                reconnect_count: epics.reconnect_count,
+                message_counts: epics.message_counts,
+                rtt_min: epics.rtt_min,
+                rtt_avg: epics.rtt_avg,
+                rtt_max: epics.rtt_max,
+            },
+            counters,
+        )
+    }
+
+    pub async fn run(mut self) -> Result<(), Error> {
+        loop {
+            sleep(UPDATE_PERIOD).await;
+            let age = self.counters.last_message.lock().unwrap().elapsed();
+            self.last_message_age
+                .request()
+                .await
+                .write(age.as_secs_f64())
+                .await;
+            write_count(&mut self.reconnect_count, &self.counters.reconnect_count).await;
+            for (variable, count) in self
+                .message_counts
+                .iter_mut()
+                .zip(self.counters.message_counts.iter())
+            {
+                write_count(variable, count).await;
+            }
+            let rtt = self.counters.keep_alive.lock().unwrap().take_rtt();
+            if let Some((min, avg, max)) = rtt {
+                self.rtt_min.request().await.write(min).await;
+                self.rtt_avg.request().await.write(avg).await;
+                self.rtt_max.request().await.write(max).await;
+            }
+        }
+    }
+}
+
+async fn write_count(variable: &mut Variable<i32>, count: &AtomicU64) {
+    let value = count.load(Ordering::Relaxed).min(i32::MAX as u64) as i32;
+    variable.request().await.write(value).await;
+}
+
+impl HealthCounters {
+    pub fn set_state(&self, state: ChannelState) {
+        self.channel_state.store(state as u16);
+        let connected = (state == ChannelState::Connected) as u16;
+        if self.connected.load() != connected {
+            self.connected.store(connected);
+        }
+    }
+    pub fn report_reconnect(&self) {
+        self.reconnect_count.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Register keep-alive message being sent and get its sequence number.
+    pub fn start_keep_alive(&self) -> u32 {
+        self.keep_alive.lock().unwrap().start()
+    }
+    pub fn report_keep_alive_ack(&self, seq: u32) {
+        self.keep_alive.lock().unwrap().ack(seq)
+    }
+    pub fn report_message(&self, msg: &McuMsgRef<'_>) {
+        *self.last_message.lock().unwrap() = Instant::now();
+        self.message_counts[msg.index()].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl KeepAlive {
+    fn new() -> Self {
+        let mut rtt = TimeStat::new("keep_alive_rtt".into());
+        // Values are published as PVs, so don't print them.
+        rtt.print_period = Duration::MAX;
+        Self {
+            next_seq: 0,
+            pending: None,
+            rtt,
+        }
+    }
+    fn start(&mut self) -> u32 {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.pending = Some((seq, Instant::now()));
+        seq
+    }
+    fn ack(&mut self, seq: u32) {
+        match self.pending {
+            Some((pending_seq, sent)) if pending_seq == seq => {
+                self.rtt.sample_since(sent);
+                self.pending = None;
+            }
+            _ => log::debug!("Unexpected keep-alive acknowledgement: {}", seq),
+        }
+    }
+    /// Take minimum, average and maximum round-trip time in milliseconds.
+    fn take_rtt(&mut self) -> Option<(f64, f64, f64)> {
+        if self.rtt.count() > 0 {
+            let values = (self.rtt.min(), self.rtt.avg(), self.rtt.max());
+            self.rtt.reset();
+            Some(values)
+        } else {
+            None
+        }
+    }
+}
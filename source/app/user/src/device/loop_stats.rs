@@ -0,0 +1,76 @@
+use super::Error;
+use crate::epics;
+use common::config::LOOP_HIST_BINS;
+use ferrite::TypedVariable as Variable;
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    StreamExt,
+};
+
+const STATS_BUFFER_SIZE: usize = 4;
+
+/// MCU control loop timing histograms.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoopStats {
+    pub cycle: [u32; LOOP_HIST_BINS],
+    pub wait_ready: [u32; LOOP_HIST_BINS],
+    /// Maximum values in microseconds.
+    pub cycle_max: u32,
+    pub wait_ready_max: u32,
+}
+
+pub struct Loop {
+    cycle: Variable<[i32]>,
+    wait_ready: Variable<[i32]>,
+    cycle_max: Variable<i32>,
+    wait_ready_max: Variable<i32>,
+    channel: Receiver<LoopStats>,
+}
+
+pub type LoopHandle = Sender<LoopStats>;
+
+impl Loop {
+    pub fn new(epics: epics::Loop) -> (Self, LoopHandle) {
+        let (sender, receiver) = channel(STATS_BUFFER_SIZE);
+        (
+            Self {
+                cycle: epics.cycle_hist,
+                wait_ready: epics.wait_ready_hist,
+                cycle_max: epics.cycle_max,
+                wait_ready_max: epics.wait_ready_max,
+                channel: receiver,
+            },
+            sender,
+        )
+    }
+    pub async fn run(mut self) -> Result<(), Error> {
+        loop {
+            let stats = match self.channel.next().await {
+                Some(value) => value,
+                None => break Err(Error::ChannelClosed),
+            };
+            // Counts are saturated to fit into EPICS long.
+            let to_long = |c: u32| c.min(i32::MAX as u32) as i32;
+            self.cycle
+                .request()
+                .await
+                .write_from(stats.cycle.into_iter().map(to_long))
+                .await;
+            self.wait_ready
+                .request()
+                .await
+                .write_from(stats.wait_ready.into_iter().map(to_long))
+                .await;
+            self.cycle_max
+                .request()
+                .await
+                .write(to_long(stats.cycle_max))
+                .await;
+            self.wait_ready_max
+                .request()
+                .await
+                .write(to_long(stats.wait_ready_max))
+                .await;
+        }
+    }
+}
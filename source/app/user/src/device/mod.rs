@@ -3,66 +3,188 @@ mod ao;
 mod debug;
 mod dio;
 mod dispatch;
+mod health;
+mod loop_stats;
+mod rates;
+mod skifio;
+mod supervisor;
+mod tasks;
 
 use crate::{channel::Channel, epics::Epics, utils::misc::unzip_array};
 use common::config;
-use futures::future::{try_join_all, FutureExt};
+use futures::future::{select, Either};
+use std::{future::Future, io, pin::pin};
+use thiserror::Error;
 
 use ai::Ai;
 use ao::Ao;
 use debug::Debug;
 use dio::{Di, Do};
-use dispatch::Dispatcher;
-use tokio::spawn;
+use dispatch::Handles;
+use health::Health;
+use loop_stats::Loop;
+use rates::Rates;
+use skifio::Skifio;
+use supervisor::{StopGuard, StopHandle, Supervisor};
+use tasks::Tasks;
+use tokio::{
+    spawn,
+    task::{JoinError, JoinSet},
+};
 
-#[derive(Clone, Debug)]
+#[derive(Debug, Error)]
 pub enum Error {
+    #[error("Channel to MCU is disconnected")]
     Disconnected,
+    /// Device was requested to stop.
+    #[error("Device is stopped")]
+    Stopped,
+    #[error("I/O error: {0}")]
+    Io(io::Error),
+    #[error("Cannot parse message from MCU: {0:?}")]
+    Parse(flatty::Error),
+    /// MCU reported an error.
+    #[error("MCU error {code}: {message}")]
+    Mcu { code: u8, message: String },
+    /// Channel between device tasks is closed.
+    #[error("Internal channel is closed")]
+    ChannelClosed,
+    #[error("Task failed: {0}")]
+    Task(#[from] JoinError),
 }
 
-pub struct Device<C: Channel> {
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset => Self::Disconnected,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+pub struct Device {
     ao: Ao,
+    ao_readback: Ai,
     ais: [Ai; config::AI_COUNT],
     di: Di,
     do_: Do,
-    dispatcher: Dispatcher<C>,
+    skifio: Skifio,
+    loop_: Loop,
+    tasks: Tasks,
+    rates: Rates,
+    health: Health,
+    supervisor: Supervisor,
+    /// Device is requested to stop when it or its running future is dropped.
+    stop_guard: StopGuard,
 }
 
-impl<C: Channel> Device<C> {
-    pub async fn new(channel: C, epics: Epics) -> Self {
+impl Device {
+    pub fn new(epics: Epics) -> (Self, StopHandle) {
         let (ao, ao_handle) = Ao::new(epics.ao);
+        let (ao_readback, ao_readback_handle) = Ai::new(epics.ao_readback);
         let (ais, ai_handles) = unzip_array(epics.ais.map(Ai::new));
         let (di, di_handle) = Di::new(epics.di);
         let (do_, do_handle) = Do::new(epics.do_);
-        let debug_handle = Debug::new(epics.debug);
-        let dispatcher = Dispatcher::new(
-            channel,
+        let (skifio, skifio_handle) = Skifio::new(epics.skifio);
+        let (loop_, loop_handle) = Loop::new(epics.loop_);
+        let (tasks, tasks_handle) = Tasks::new(epics.tasks);
+        let (rates, rates_handle) = Rates::new(epics.rates);
+        let (health, health_handle) = Health::new(epics.health);
+        let debug_handle = Debug::new(
+            epics.debug,
+            ao_readback.max_fill(),
+            ais.each_ref().map(Ai::max_fill),
+        );
+        let handles = Handles::new(
             ao_handle,
+            ao_readback_handle,
             ai_handles,
             di_handle,
             do_handle,
+            skifio_handle,
+            loop_handle,
+            tasks_handle,
+            rates_handle,
             debug_handle,
+        );
+        let (supervisor, stop_handle, stop_guard) = Supervisor::new(handles, health_handle);
+        (
+            Self {
+                ao,
+                ao_readback,
+                ais,
+                di,
+                do_,
+                skifio,
+                loop_,
+                tasks,
+                rates,
+                health,
+                supervisor,
+                stop_guard,
+            },
+            stop_handle,
         )
-        .await;
-        Self {
+    }
+
+    /// Run device using `open` to (re-)establish channels to MCU by their ids.
+    ///
+    /// Returns `Ok` after device was stopped. If returned future is dropped then device is stopped
+    /// and says goodbye to MCU in background.
+    pub async fn run<C, F, R>(self, open: F) -> Result<(), Error>
+    where
+        C: Channel,
+        F: FnMut(u32) -> R + Send + 'static,
+        R: Future<Output = Result<C, io::Error>> + Send,
+    {
+        let Self {
             ao,
+            ao_readback,
             ais,
             di,
             do_,
-            dispatcher,
+            skifio,
+            loop_,
+            tasks,
+            rates,
+            health,
+            supervisor,
+            stop_guard,
+        } = self;
+        let mut parts = JoinSet::new();
+        parts.spawn(ao.run());
+        parts.spawn(ao_readback.run());
+        for ai in ais {
+            parts.spawn(ai.run());
         }
-    }
-
-    pub async fn run(self) {
-        let res = try_join_all([
-            spawn(self.ao.run()).map(Result::unwrap),
-            spawn(try_join_all(self.ais.map(|adc| adc.run())).map(|r| r.map(|_| ())))
-                .map(Result::unwrap),
-            spawn(self.di.run()).map(Result::unwrap),
-            spawn(self.do_.run()).map(Result::unwrap),
-            spawn(self.dispatcher.run()).map(Result::unwrap),
-        ])
+        parts.spawn(di.run());
+        parts.spawn(do_.run());
+        parts.spawn(skifio.run());
+        parts.spawn(loop_.run());
+        parts.spawn(tasks.run());
+        parts.spawn(rates.run());
+        parts.spawn(health.run());
+        let res = spawn(async move {
+            let parts_done = async {
+                while let Some(res) = parts.join_next().await {
+                    flatten(res)?;
+                }
+                Ok(())
+            };
+            // Device parts run forever, so device is done when supervisor is stopped or any part fails.
+            let res = match select(pin!(supervisor.run(open)), pin!(parts_done)).await {
+                Either::Left((res, _)) | Either::Right((res, _)) => res,
+            };
+            // Remaining parts are aborted on drop.
+            drop(parts);
+            res
+        })
         .await;
-        log::warn!("Stopping device: {:?}", res);
+        drop(stop_guard);
+        flatten(res)
     }
 }
+
+fn flatten(res: Result<Result<(), Error>, JoinError>) -> Result<(), Error> {
+    res?
+}
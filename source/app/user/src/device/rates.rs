@@ -0,0 +1,111 @@
+use super::Error;
+use crate::epics;
+use common::{config::SKIFIO_STATE_PERIOD, rates::RateHistory};
+use ferrite::TypedVariable as Variable;
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    StreamExt,
+};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const COUNTERS_BUFFER_SIZE: usize = 4;
+
+const COUNTER_COUNT: usize = 4;
+
+/// Counters are sent by MCU control loop once per `SKIFIO_STATE_PERIOD`.
+/// If they are late then the loop is stalled and counters are considered unchanged.
+const COUNTERS_MAX_DELAY: Duration = SKIFIO_STATE_PERIOD.saturating_mul(2);
+
+/// MCU event counters since the last statistics reset.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Counters {
+    pub sync: u32,
+    pub samples: u32,
+    pub crc_errors: u32,
+    pub ao_lost: u32,
+    /// MCU time in microseconds, wraps around.
+    pub time_us: u32,
+}
+
+impl Counters {
+    fn values(&self) -> [u32; COUNTER_COUNT] {
+        [self.sync, self.samples, self.crc_errors, self.ao_lost]
+    }
+}
+
+struct Window {
+    length: Duration,
+    /// Rate PVs in order of `Counters::values`.
+    rates: [Variable<f64>; COUNTER_COUNT],
+}
+
+/// Publishes rates of MCU events averaged over windows set in database.
+///
+/// Rates keep being updated when counters are late, so they fall to zero when MCU control loop stalls.
+pub struct Rates {
+    epics: Vec<epics::RateWindow>,
+    windows: Vec<Window>,
+    channel: Receiver<Counters>,
+}
+
+pub type RatesHandle = Sender<Counters>;
+
+impl Rates {
+    pub fn new(epics: epics::Rates) -> (Self, RatesHandle) {
+        let (sender, receiver) = channel(COUNTERS_BUFFER_SIZE);
+        (
+            Self {
+                epics: epics.windows,
+                windows: Vec::new(),
+                channel: receiver,
+            },
+            sender,
+        )
+    }
+
+    /// Read window lengths from database.
+    async fn init_windows(&mut self) {
+        for mut window in self.epics.drain(..) {
+            let value = window.length.wait().await.read().await;
+            let length = match Duration::try_from_secs_f64(value) {
+                Ok(length) if !length.is_zero() => length,
+                _ => {
+                    log::error!("Bad rate window length: {} s, window is disabled", value);
+                    continue;
+                }
+            };
+            self.windows.push(Window {
+                length,
+                rates: [
+                    window.sync,
+                    window.samples,
+                    window.crc_errors,
+                    window.ao_lost,
+                ],
+            });
+        }
+    }
+
+    pub async fn run(mut self) -> Result<(), Error> {
+        self.init_windows().await;
+        let max_window = self.windows.iter().map(|window| window.length).max();
+        let mut history = RateHistory::new(max_window.unwrap_or_default());
+        loop {
+            match timeout(COUNTERS_MAX_DELAY, self.channel.next()).await {
+                Ok(Some(counters)) => history.push(counters.time_us, counters.values()),
+                Ok(None) => break Err(Error::ChannelClosed),
+                Err(_) => history.stall(COUNTERS_MAX_DELAY),
+            }
+            for window in self.windows.iter_mut() {
+                let rates = match history.rates(window.length) {
+                    Some(rates) => rates,
+                    None => continue,
+                };
+                for (var, rate) in window.rates.iter_mut().zip(rates) {
+                    var.request().await.write(rate).await;
+                }
+            }
+        }
+    }
+}
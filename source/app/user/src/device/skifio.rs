@@ -0,0 +1,48 @@
+use super::Error;
+use crate::epics;
+use ferrite::TypedVariable as Variable;
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    StreamExt,
+};
+
+const STATE_BUFFER_SIZE: usize = 4;
+
+/// SkifIO board temperature and status.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SkifioState {
+    pub temp: i8,
+    pub status: u8,
+}
+
+pub struct Skifio {
+    temp: Variable<f64>,
+    status: Variable<u32>,
+    channel: Receiver<SkifioState>,
+}
+
+pub type SkifioHandle = Sender<SkifioState>;
+
+impl Skifio {
+    pub fn new(epics: epics::Skifio) -> (Self, SkifioHandle) {
+        let (sender, receiver) = channel(STATE_BUFFER_SIZE);
+        (
+            Self {
+                temp: epics.temp,
+                status: epics.status,
+                channel: receiver,
+            },
+            sender,
+        )
+    }
+    pub async fn run(mut self) -> Result<(), Error> {
+        loop {
+            let state = match self.channel.next().await {
+                Some(value) => value,
+                None => break Err(Error::ChannelClosed),
+            };
+            self.temp.request().await.write(state.temp as f64).await;
+            self.status.request().await.write(state.status as u32).await;
+        }
+    }
+}
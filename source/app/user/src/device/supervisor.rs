@@ -0,0 +1,167 @@
+use super::{
+    dispatch::{Dispatcher, Handles},
+    health::{ChannelState, HealthHandle},
+    Error,
+};
+use crate::channel::{Captured, Channel, Recorder};
+use common::config;
+use futures::future::{pending, select, try_join, Either};
+use std::{
+    future::Future,
+    io,
+    pin::pin,
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+use tokio::{sync::watch, time::sleep};
+
+/// Initial delay between attempts to establish channel.
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(100);
+/// Maximum delay between attempts to establish channel.
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(5);
+/// Maximum time to wait for device to say goodbye to MCU on stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Re-establishes channel to MCU after it was lost.
+pub struct Supervisor {
+    handles: Handles,
+    health: HealthHandle,
+    /// Records messages of all connections if capture is enabled.
+    recorder: Option<Arc<Recorder>>,
+    stop: watch::Receiver<bool>,
+    stopped: mpsc::Sender<()>,
+}
+
+/// Requests orderly stop of the device from outside of async runtime.
+pub struct StopHandle {
+    stop: Arc<watch::Sender<bool>>,
+    stopped: mpsc::Receiver<()>,
+}
+
+/// Requests device to stop when dropped, without waiting for it.
+pub struct StopGuard {
+    stop: Arc<watch::Sender<bool>>,
+}
+
+impl Supervisor {
+    pub fn new(handles: Handles, health: HealthHandle) -> (Self, StopHandle, StopGuard) {
+        let (stop_sender, stop) = watch::channel(false);
+        let stop_sender = Arc::new(stop_sender);
+        let (stopped, stopped_receiver) = mpsc::channel();
+        (
+            Self {
+                handles,
+                health,
+                recorder: Recorder::from_env().map(Arc::new),
+                stop,
+                stopped,
+            },
+            StopHandle {
+                stop: stop_sender.clone(),
+                stopped: stopped_receiver,
+            },
+            StopGuard { stop: stop_sender },
+        )
+    }
+
+    pub async fn run<C, F, R>(mut self, mut open: F) -> Result<(), Error>
+    where
+        C: Channel,
+        F: FnMut(u32) -> R,
+        R: Future<Output = Result<C, io::Error>>,
+    {
+        let mut delay = RECONNECT_DELAY_MIN;
+        let mut connected_before = false;
+        loop {
+            log::info!("Establish channel");
+            self.health.set_state(ChannelState::Connecting);
+            let opened = try_join(
+                open(config::CONTROL_CHANNEL_ID),
+                open(config::DATA_CHANNEL_ID),
+            );
+            let (control, data) = match until_stop(&mut self.stop, opened).await {
+                Some(Ok(channels)) => channels,
+                Some(Err(err)) => {
+                    log::debug!("Cannot establish channel: {}", err);
+                    self.health.set_state(ChannelState::Disconnected);
+                    if until_stop(&mut self.stop, sleep(delay)).await.is_none() {
+                        break;
+                    }
+                    delay = (2 * delay).min(RECONNECT_DELAY_MAX);
+                    continue;
+                }
+                None => break,
+            };
+            log::info!("Connection established");
+            delay = RECONNECT_DELAY_MIN;
+            if connected_before {
+                self.health.report_reconnect();
+            }
+            connected_before = true;
+            self.health.set_state(ChannelState::Connected);
+
+            let res = Dispatcher::new(
+                Captured::new(control, config::CONTROL_CHANNEL_ID, self.recorder.clone()),
+                Captured::new(data, config::DATA_CHANNEL_ID, self.recorder.clone()),
+                &mut self.handles,
+                self.health.clone(),
+                self.stop.clone(),
+            )
+            .run()
+            .await;
+
+            self.health.set_state(ChannelState::Disconnected);
+            match res {
+                Err(Error::Stopped) => break,
+                // Device tasks are broken, so there is no sense to reconnect.
+                Err(Error::ChannelClosed) => return Err(Error::ChannelClosed),
+                Err(err) => log::warn!("Connection lost: {}", err),
+                Ok(()) => log::warn!("Connection closed"),
+            }
+            if until_stop(&mut self.stop, sleep(delay)).await.is_none() {
+                break;
+            }
+        }
+        self.health.set_state(ChannelState::Disconnected);
+        log::info!("Device stopped");
+        self.stopped.send(()).ok();
+        Ok(())
+    }
+}
+
+impl StopHandle {
+    /// Request device to stop and wait until it says goodbye to MCU.
+    pub fn stop(self) {
+        if self.stop.send(true).is_err() {
+            // Device isn't running.
+            return;
+        }
+        if self.stopped.recv_timeout(STOP_TIMEOUT).is_err() {
+            log::warn!("Device stop timed out");
+        }
+    }
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        self.stop.send_replace(true);
+    }
+}
+
+/// Wait until device is requested to stop.
+async fn wait_stop(stop: &mut watch::Receiver<bool>) {
+    while !*stop.borrow_and_update() {
+        if stop.changed().await.is_err() {
+            // Stop handle and guard were dropped, so stop will never be requested.
+            pending::<()>().await;
+        }
+    }
+}
+
+/// Run `fut` to completion unless device is requested to stop before.
+pub async fn until_stop<F: Future>(stop: &mut watch::Receiver<bool>, fut: F) -> Option<F::Output> {
+    match select(pin!(fut), pin!(wait_stop(stop))).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
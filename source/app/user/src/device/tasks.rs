@@ -0,0 +1,92 @@
+use super::Error;
+use crate::epics;
+use common::{
+    config::MCU_TASKS,
+    protocol::{TaskUsage, TASK_NAME_LEN},
+};
+use ferrite::TypedVariable as Variable;
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    StreamExt,
+};
+use std::time::Instant;
+
+const STATS_BUFFER_SIZE: usize = 4;
+
+const MCU_TASK_COUNT: usize = MCU_TASKS.len();
+
+/// Resource usage of MCU tasks.
+#[derive(Clone, Debug, Default)]
+pub struct TaskStats {
+    pub run_time_freq: u32,
+    pub tasks: Vec<TaskUsage>,
+}
+
+/// Publishes CPU load and free stack of MCU tasks.
+pub struct Tasks {
+    cpu_load: [Variable<f64>; MCU_TASK_COUNT],
+    stack_free: [Variable<i32>; MCU_TASK_COUNT],
+    channel: Receiver<TaskStats>,
+    /// Run time of tasks and time when it was received.
+    last: Option<(TaskStats, Instant)>,
+}
+
+pub type TasksHandle = Sender<TaskStats>;
+
+fn name_bytes(name: &str) -> [u8; TASK_NAME_LEN] {
+    let mut buf = [0; TASK_NAME_LEN];
+    buf.iter_mut().zip(name.bytes()).for_each(|(b, c)| *b = c);
+    buf
+}
+
+impl Tasks {
+    pub fn new(epics: epics::Tasks) -> (Self, TasksHandle) {
+        let (sender, receiver) = channel(STATS_BUFFER_SIZE);
+        (
+            Self {
+                cpu_load: epics.cpu_load,
+                stack_free: epics.stack_free,
+                channel: receiver,
+                last: None,
+            },
+            sender,
+        )
+    }
+    pub async fn run(mut self) -> Result<(), Error> {
+        loop {
+            let stats = match self.channel.next().await {
+                Some(value) => value,
+                None => break Err(Error::ChannelClosed),
+            };
+            let now = Instant::now();
+            for (index, task) in MCU_TASKS.iter().enumerate() {
+                let name = name_bytes(task.name);
+                let task = match stats.tasks.iter().find(|task| task.name == name) {
+                    Some(task) => task,
+                    None => continue,
+                };
+                if task.stack_free != u32::MAX {
+                    self.stack_free[index]
+                        .request()
+                        .await
+                        .write(task.stack_free.min(i32::MAX as u32) as i32)
+                        .await;
+                }
+                // CPU load is measured between two consecutive messages.
+                if let Some((last_stats, last_time)) = &self.last {
+                    if let Some(last) = last_stats.tasks.iter().find(|task| task.name == name) {
+                        let ticks = task.run_time.wrapping_sub(last.run_time) as f64;
+                        let elapsed = (now - *last_time).as_secs_f64();
+                        let load = ticks / stats.run_time_freq as f64 / elapsed;
+                        self.cpu_load[index]
+                            .request()
+                            .await
+                            .write(100.0 * load)
+                            .await;
+                    }
+                }
+            }
+            self.last = Some((stats, now));
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use common::config::AI_COUNT;
+use common::{
+    config::{AI_COUNT, MCU_TASKS},
+    protocol::MCU_MSG_NAMES,
+};
 use ferrite::{
     registry::{CheckEmptyError, GetDowncastError},
     Context, Registry, TypedVariable as Variable,
@@ -18,22 +21,84 @@ pub struct Ao {
     pub add: Variable<f64>,
     pub next_cycle: Variable<u16>,
     pub next_ready: Variable<u16>,
+    pub readback_enable: Variable<u16>,
 }
 
 pub struct Ai {
     pub waveform: Variable<[f64]>,
+    /// High watermark of IOC-side buffer occupancy in points.
+    pub max_fill: Variable<i32>,
+}
+
+pub struct Skifio {
+    pub temp: Variable<f64>,
+    pub status: Variable<u32>,
+}
+
+/// Control loop timing histograms.
+pub struct Loop {
+    pub cycle_hist: Variable<[i32]>,
+    pub wait_ready_hist: Variable<[i32]>,
+    pub cycle_max: Variable<i32>,
+    pub wait_ready_max: Variable<i32>,
+}
+
+pub struct Tasks {
+    pub cpu_load: [Variable<f64>; MCU_TASKS.len()],
+    pub stack_free: [Variable<i32>; MCU_TASKS.len()],
+}
+
+/// Rates of MCU events per second averaged over a window.
+pub struct RateWindow {
+    /// Window length in seconds set in database.
+    pub length: Variable<f64>,
+    /// Effective frequency of 10 kHz sync signal.
+    pub sync: Variable<f64>,
+    /// Effective sample frequency.
+    pub samples: Variable<f64>,
+    pub crc_errors: Variable<f64>,
+    /// Lost AO points per second.
+    pub ao_lost: Variable<f64>,
+}
+
+/// Rate windows, their number and lengths are defined in `rate.substitutions`.
+pub struct Rates {
+    pub windows: Vec<RateWindow>,
+}
+
+pub struct Health {
+    pub connected: Variable<u16>,
+    pub channel_state: Variable<u16>,
+    pub last_message_age: Variable<f64>,
+    pub reconnect_count: Variable<i32>,
+    pub message_counts: [Variable<i32>; MCU_MSG_NAMES.len()],
+    pub rtt_min: Variable<f64>,
+    pub rtt_avg: Variable<f64>,
+    pub rtt_max: Variable<f64>,
 }
 
 pub struct Debug {
+    /// Reset all MCU statistics.
     pub reset_stats: Variable<u16>,
+    pub reset_stats_global: Variable<u16>,
+    pub reset_stats_ao: Variable<u16>,
+    pub reset_stats_ais: [Variable<u16>; AI_COUNT],
+    pub reset_stats_crc_errors: Variable<u16>,
+    pub reset_stats_ioc_drops: Variable<u16>,
 }
 
 /// EPICS interface
 pub struct Epics {
     pub ao: Ao,
+    pub ao_readback: Ai,
     pub ais: [Ai; AI_COUNT],
     pub do_: Variable<u32>,
     pub di: Variable<u32>,
+    pub skifio: Skifio,
+    pub loop_: Loop,
+    pub tasks: Tasks,
+    pub rates: Rates,
+    pub health: Health,
     pub debug: Debug,
 }
 
@@ -44,22 +109,115 @@ impl Ao {
             add: reg.remove_downcast_suffix("Ao0Add")?,
             next_ready: reg.remove_downcast_suffix("AoNextReady")?,
             next_cycle: reg.remove_downcast_suffix("AoNextCycle")?,
+            readback_enable: reg.remove_downcast_suffix("Ao0ReadbackEnable")?,
         })
     }
 }
 
 impl Ai {
     fn new(reg: &mut Registry, index: usize) -> Result<Self, Error> {
+        Self::with_name(reg, &format!("Ai{}", index))
+    }
+    fn with_name(reg: &mut Registry, name: &str) -> Result<Self, Error> {
         Ok(Self {
-            waveform: reg.remove_downcast_suffix(&format!("Ai{}", index))?,
+            waveform: reg.remove_downcast_suffix(name)?,
+            max_fill: reg.remove_downcast_suffix(&format!("{}BufferFill", name))?,
+        })
+    }
+}
+
+impl Skifio {
+    fn new(reg: &mut Registry) -> Result<Self, Error> {
+        Ok(Self {
+            temp: reg.remove_downcast_suffix("SkifioTemp")?,
+            status: reg.remove_downcast_suffix("SkifioStatus")?,
+        })
+    }
+}
+
+impl Loop {
+    fn new(reg: &mut Registry) -> Result<Self, Error> {
+        Ok(Self {
+            cycle_hist: reg.remove_downcast_suffix("LoopCycleHist")?,
+            wait_ready_hist: reg.remove_downcast_suffix("LoopWaitReadyHist")?,
+            cycle_max: reg.remove_downcast_suffix("LoopCycleMax")?,
+            wait_ready_max: reg.remove_downcast_suffix("LoopWaitReadyMax")?,
+        })
+    }
+}
+
+impl Tasks {
+    fn new(reg: &mut Registry) -> Result<Self, Error> {
+        let mut cpu_load = Vec::new();
+        let mut stack_free = Vec::new();
+        for task in MCU_TASKS {
+            cpu_load.push(reg.remove_downcast_suffix(&format!("TaskCpu{}", task.pv_name))?);
+            stack_free.push(reg.remove_downcast_suffix(&format!("TaskStackFree{}", task.pv_name))?);
+        }
+        Ok(Self {
+            cpu_load: cpu_load.try_into().ok().unwrap(),
+            stack_free: stack_free.try_into().ok().unwrap(),
+        })
+    }
+}
+
+impl RateWindow {
+    fn new(reg: &mut Registry, index: usize) -> Result<Self, Error> {
+        Ok(Self {
+            length: reg.remove_downcast_suffix(&format!("RateWindow{}", index))?,
+            sync: reg.remove_downcast_suffix(&format!("RateSync{}", index))?,
+            samples: reg.remove_downcast_suffix(&format!("RateSample{}", index))?,
+            crc_errors: reg.remove_downcast_suffix(&format!("RateCrcError{}", index))?,
+            ao_lost: reg.remove_downcast_suffix(&format!("RateAoLost{}", index))?,
+        })
+    }
+}
+
+impl Rates {
+    fn new(reg: &mut Registry) -> Result<Self, Error> {
+        // Windows are numbered from zero without gaps, so the first missing one ends the list.
+        // Records left in registry are reported as unused.
+        let mut windows = Vec::new();
+        while let Ok(window) = RateWindow::new(reg, windows.len()) {
+            windows.push(window);
+        }
+        Ok(Self { windows })
+    }
+}
+
+impl Health {
+    fn new(reg: &mut Registry) -> Result<Self, Error> {
+        let mut message_counts = Vec::new();
+        for name in MCU_MSG_NAMES {
+            message_counts.push(reg.remove_downcast_suffix(&format!("MsgCount{}", name))?);
+        }
+        Ok(Self {
+            connected: reg.remove_downcast_suffix("Connected")?,
+            channel_state: reg.remove_downcast_suffix("ChannelState")?,
+            last_message_age: reg.remove_downcast_suffix("LastMsgAge")?,
+            reconnect_count: reg.remove_downcast_suffix("ReconnectCount")?,
+            message_counts: message_counts.try_into().ok().unwrap(),
+            rtt_min: reg.remove_downcast_suffix("KeepAliveRttMin")?,
+            rtt_avg: reg.remove_downcast_suffix("KeepAliveRttAvg")?,
+            rtt_max: reg.remove_downcast_suffix("KeepAliveRttMax")?,
         })
     }
 }
 
 impl Debug {
     fn new(reg: &mut Registry) -> Result<Self, Error> {
+        let mut reset_stats_ais = Vec::new();
+        for index in 0..AI_COUNT {
+            reset_stats_ais
+                .push(reg.remove_downcast_suffix(&format!("DebugResetStatsAi{}", index))?);
+        }
         Ok(Self {
             reset_stats: reg.remove_downcast_suffix("DebugResetStats")?,
+            reset_stats_global: reg.remove_downcast_suffix("DebugResetStatsGlobal")?,
+            reset_stats_ao: reg.remove_downcast_suffix("DebugResetStatsAo")?,
+            reset_stats_ais: reset_stats_ais.try_into().ok().unwrap(),
+            reset_stats_crc_errors: reg.remove_downcast_suffix("DebugResetStatsCrcErrors")?,
+            reset_stats_ioc_drops: reg.remove_downcast_suffix("DebugResetStatsIocDrops")?,
         })
     }
 }
@@ -73,9 +231,15 @@ impl Epics {
         }
         let self_ = Self {
             ao: Ao::new(reg)?,
+            ao_readback: Ai::with_name(reg, "Ao0Readback")?,
             ais: ais.try_into().ok().unwrap(),
             do_: reg.remove_downcast_suffix("Do")?,
             di: reg.remove_downcast_suffix("Di")?,
+            skifio: Skifio::new(reg)?,
+            loop_: Loop::new(reg)?,
+            tasks: Tasks::new(reg)?,
+            rates: Rates::new(reg)?,
+            health: Health::new(reg)?,
             debug: Debug::new(reg)?,
         };
         ctx.registry.check_empty()?;
@@ -3,10 +3,10 @@ mod device;
 mod epics;
 mod utils;
 
-#[cfg(feature = "tcp")]
 use common::config;
 use ferrite::{entry_point, Context};
 use macro_rules_attribute::apply;
+use std::env;
 use tokio::runtime;
 
 use device::Device;
@@ -31,24 +31,52 @@ fn app_main(mut ctx: Context) {
 async fn run(ctx: Context) {
     log::info!("Start IOC");
 
-    log::info!("Establish channel");
-    #[cfg(feature = "tcp")]
-    let channel = channel::connect((config::CHANNEL_HOST, config::CHANNEL_PORT))
-        .await
-        .unwrap();
-    #[cfg(feature = "rpmsg")]
-    let channel = channel::Rpmsg::open("/dev/ttyRPMSG0")
-        .await
-        .expect("Error while opening RPMSG device");
-    log::info!("Connection established");
-
     log::info!("Get EPICS PVs");
     let epics = Epics::new(ctx).unwrap();
 
     log::info!("Init device");
-    let device = Device::new(channel, epics).await;
+    let (device, stop) = Device::new(epics);
+    utils::exit::at_exit(move || {
+        log::info!("Stop device");
+        stop.stop();
+    });
     log::info!("Run device");
-    device.run().await;
+    let res = match env::var_os(config::REPLAY_ENV) {
+        Some(path) => {
+            log::info!("Replay capture {:?}", path);
+            device
+                .run(move |id| channel::Replay::open(path.clone(), id))
+                .await
+        }
+        None => run_connected(device).await,
+    };
 
-    log::info!("device stopped");
+    match res {
+        Ok(()) => log::info!("Device stopped"),
+        Err(err) => log::error!("Device failed: {}", err),
+    }
+}
+
+/// Run device connected to MCU.
+#[allow(clippy::let_and_return)]
+async fn run_connected(device: Device) -> Result<(), device::Error> {
+    #[cfg(feature = "loopback")]
+    let res = {
+        log::info!("Start fake MCU");
+        fakedev::generator::spawn_dummy(fakedev::run());
+        device.run(channel::connect_loopback).await
+    };
+    #[cfg(all(feature = "unix", not(feature = "loopback")))]
+    let res = device
+        .run(|id| channel::connect_unix(config::channel_socket(id)))
+        .await;
+    #[cfg(all(feature = "tcp", not(any(feature = "unix", feature = "loopback"))))]
+    let res = device
+        .run(|id| channel::connect((config::CHANNEL_HOST, config::channel_port(id))))
+        .await;
+    #[cfg(feature = "rpmsg")]
+    let res = device
+        .run(|id| channel::Rpmsg::open(channel::rpmsg::device_path(id)))
+        .await;
+    res
 }
@@ -0,0 +1,20 @@
+use std::{ffi::c_void, os::raw::c_int};
+
+type ExitFn = Box<dyn FnOnce() + Send>;
+
+extern "C" {
+    /// Provided by EPICS `libCom` which IOC is linked with.
+    fn epicsAtExit(func: extern "C" fn(*mut c_void), arg: *mut c_void) -> c_int;
+}
+
+extern "C" fn call_exit_fn(arg: *mut c_void) {
+    let func = unsafe { Box::from_raw(arg as *mut ExitFn) };
+    func();
+}
+
+/// Register function to be called on IOC exit.
+pub fn at_exit<F: FnOnce() + Send + 'static>(func: F) {
+    let arg = Box::into_raw(Box::new(Box::new(func) as ExitFn));
+    let r = unsafe { epicsAtExit(call_exit_fn, arg as *mut c_void) };
+    assert_eq!(r, 0, "Cannot register IOC exit hook");
+}
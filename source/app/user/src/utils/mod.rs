@@ -1,4 +1,5 @@
 pub mod double_vec;
+pub mod exit;
 pub mod misc;
 #[allow(dead_code)]
 pub mod stat;
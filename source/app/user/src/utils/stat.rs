@@ -30,7 +30,6 @@ impl Stat {
         }
     }
 
-    #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.count = 0;
         self.sum = 0.0;
@@ -51,6 +50,19 @@ impl Stat {
         }
     }
 
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+    pub fn avg(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
     pub fn print(&self) {
         println!("stat '{}':", self.name);
         println!("  count: {}", self.count);
@@ -85,4 +97,9 @@ impl TimeStat {
         }
         self.last_sample = Some(now);
     }
+
+    /// Sample time elapsed since `start`.
+    pub fn sample_since(&mut self, start: Instant) {
+        self.stat.sample(start.elapsed().as_secs_f64() * 1000.0);
+    }
 }
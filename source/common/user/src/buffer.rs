@@ -0,0 +1,44 @@
+//! Heap buffer for messages.
+
+use alloc::{vec, vec::Vec};
+use core::{
+    mem::{align_of, size_of},
+    ops::{Deref, DerefMut},
+    slice,
+};
+use flatty::Flat;
+
+/// Buffer aligned enough to store any message.
+pub struct Buffer(Vec<u64>);
+
+impl Buffer {
+    /// Buffer of at least `len` bytes.
+    pub fn new(len: usize) -> Self {
+        Self(vec![0; (len + size_of::<u64>() - 1) / size_of::<u64>()])
+    }
+    /// Buffer of at least `len` bytes suitable for message `M`.
+    pub fn for_msg<M: Flat + ?Sized>(len: usize) -> Self {
+        assert!(M::ALIGN <= align_of::<u64>());
+        Self::new(len)
+    }
+    /// Aligned copy of `data`, may be longer than it.
+    pub fn copy_from(data: &[u8]) -> Self {
+        let mut this = Self::new(data.len());
+        this[..data.len()].copy_from_slice(data);
+        this
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        let len = self.0.len() * size_of::<u64>();
+        unsafe { slice::from_raw_parts(self.0.as_ptr() as *const u8, len) }
+    }
+}
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.0.len() * size_of::<u64>();
+        unsafe { slice::from_raw_parts_mut(self.0.as_mut_ptr() as *mut u8, len) }
+    }
+}
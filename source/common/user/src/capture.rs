@@ -0,0 +1,82 @@
+//! Capture of messages passed between IOC and MCU.
+//!
+//! Capture starts with [`MAGIC`] followed by records.
+//! Each record is [`RecordHeader`] followed by raw message bytes.
+
+/// Capture signature, the last byte is format version.
+pub const MAGIC: [u8; 8] = *b"TORNCAP\x01";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// `AppMsg` sent from IOC to MCU.
+    AppToMcu = 0,
+    /// `McuMsg` sent from MCU to IOC.
+    McuToApp = 1,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RecordHeader {
+    /// Monotonic time since capture start in nanoseconds.
+    pub time_ns: u64,
+    pub direction: Direction,
+    /// Id of channel the message was passed through.
+    pub channel: u8,
+    /// Length of the message in bytes.
+    pub len: u16,
+}
+
+impl RecordHeader {
+    pub const SIZE: usize = 12;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.time_ns.to_le_bytes());
+        bytes[8] = self.direction as u8;
+        bytes[9] = self.channel;
+        bytes[10..12].copy_from_slice(&self.len.to_le_bytes());
+        bytes
+    }
+
+    /// Returns `None` if header is malformed.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let direction = match bytes[8] {
+            0 => Direction::AppToMcu,
+            1 => Direction::McuToApp,
+            _ => return None,
+        };
+        Some(Self {
+            time_ns: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            direction,
+            channel: bytes[9],
+            len: u16::from_le_bytes(bytes[10..12].try_into().unwrap()),
+        })
+    }
+}
+
+/// Iterator over records of capture stored in memory.
+///
+/// Iteration stops at the first truncated or malformed record,
+/// so capture cut off by crash can still be read.
+pub struct Records<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Records<'a> {
+    /// Returns `None` if capture doesn't start with [`MAGIC`].
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        data.strip_prefix(&MAGIC).map(|data| Self { data })
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = (RecordHeader, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header =
+            RecordHeader::from_bytes(self.data.get(..RecordHeader::SIZE)?.try_into().unwrap())?;
+        let end = RecordHeader::SIZE + header.len as usize;
+        let msg = self.data.get(RecordHeader::SIZE..end)?;
+        self.data = &self.data[end..];
+        Some((header, msg))
+    }
+}
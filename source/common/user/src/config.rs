@@ -1,4 +1,11 @@
+#[cfg(feature = "fake")]
+extern crate std;
+
+#[cfg(feature = "fake")]
+use alloc::{format, string::String};
 use core::time::Duration;
+#[cfg(feature = "fake")]
+use std::env;
 
 pub const AI_COUNT: usize = 6;
 
@@ -10,10 +17,127 @@ pub const SAMPLE_PERIOD: Duration = Duration::from_micros(100);
 pub const MAX_APP_MSG_LEN: usize = 496;
 pub const MAX_MCU_MSG_LEN: usize = 496;
 
+/// Channel for low-latency control and status messages.
+pub const CONTROL_CHANNEL_ID: u32 = 1;
+/// Channel for bulk AI/AO data.
+pub const DATA_CHANNEL_ID: u32 = 0;
+
 pub const KEEP_ALIVE_PERIOD: Duration = Duration::from_millis(100);
 pub const KEEP_ALIVE_MAX_DELAY: Duration = Duration::from_millis(200);
 
+/// MCU task reported to IOC.
+pub struct McuTask {
+    /// Name the task is spawned with, must fit `protocol::TASK_NAME_LEN`.
+    pub name: &'static str,
+    /// Name used in task PV names.
+    pub pv_name: &'static str,
+}
+
+pub const CONTROL_TASK: McuTask = McuTask {
+    name: "control",
+    pv_name: "Control",
+};
+pub const RPMSG_CTRL_READ_TASK: McuTask = McuTask {
+    name: "rpmsg_ctrl_read",
+    pv_name: "RpmsgCtrlRead",
+};
+pub const RPMSG_CTRL_WRITE_TASK: McuTask = McuTask {
+    name: "rpmsg_ctrl_write",
+    pv_name: "RpmsgCtrlWrite",
+};
+pub const RPMSG_DATA_READ_TASK: McuTask = McuTask {
+    name: "rpmsg_data_read",
+    pv_name: "RpmsgDataRead",
+};
+pub const RPMSG_DATA_WRITE_TASK: McuTask = McuTask {
+    name: "rpmsg_data_write",
+    pv_name: "RpmsgDataWrite",
+};
+/// Statistics printer, spawned only on real MCU.
+pub const STATS_TASK: McuTask = McuTask {
+    name: "stats",
+    pv_name: "Stats",
+};
+
+/// Tasks which usage is published by IOC.
+///
+/// `task.substitutions` must list their PV names.
+pub const MCU_TASKS: [McuTask; 6] = [
+    CONTROL_TASK,
+    RPMSG_CTRL_READ_TASK,
+    RPMSG_CTRL_WRITE_TASK,
+    RPMSG_DATA_READ_TASK,
+    RPMSG_DATA_WRITE_TASK,
+    STATS_TASK,
+];
+
+/// Period of sending SkifIO board temperature and status to IOC.
+pub const SKIFIO_STATE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Upper bounds of control loop timing histogram bins in microseconds.
+///
+/// The last bin counts all values greater or equal to the last bound.
+pub const LOOP_HIST_BOUNDS_US: [u32; LOOP_HIST_BINS - 1] = [
+    10, 20, 50, 80, 90, 95, 99, 101, 105, 110, 120, 150, 200, 500, 1000,
+];
+/// Number of control loop timing histogram bins.
+pub const LOOP_HIST_BINS: usize = 16;
+
+/// Environment variable with path to capture messages passed through IOC channels to.
+pub const CAPTURE_ENV: &str = "TORNADO_CAPTURE";
+/// Environment variable with path to capture to replay instead of connecting to MCU.
+pub const REPLAY_ENV: &str = "TORNADO_REPLAY";
+
+/// RPMSG endpoint device path prefix, channel id is appended to it.
+///
+/// Endpoints must be bound to `rpmsg_char` driver, TTY devices are not supported
+/// because they don't preserve message boundaries.
+/// Linux numbers devices in order of endpoint announcement, not by MCU endpoint id,
+/// so this default may not match and the mapping can be set by `RPMSG_CHANNELS_ENV`.
+#[cfg(feature = "real")]
+pub const RPMSG_DEVICE: &str = "/dev/rpmsg";
+/// Environment variable overriding `RPMSG_DEVICE`.
+#[cfg(feature = "real")]
+pub const RPMSG_DEVICE_ENV: &str = "TORNADO_RPMSG_DEVICE";
+/// Environment variable with comma-separated `<channel id>=<device path>` pairs,
+/// e.g. `0=/dev/rpmsg1,1=/dev/rpmsg0`. Channels not listed use `RPMSG_DEVICE`.
+#[cfg(feature = "real")]
+pub const RPMSG_CHANNELS_ENV: &str = "TORNADO_RPMSG_CHANNELS";
+
 #[cfg(feature = "fake")]
 pub const CHANNEL_HOST: &str = "localhost";
+/// Base TCP port, channel id is added to it.
 #[cfg(feature = "fake")]
 pub const CHANNEL_PORT: u16 = 4578;
+/// Environment variable overriding `CHANNEL_PORT`.
+#[cfg(feature = "fake")]
+pub const CHANNEL_PORT_ENV: &str = "TORNADO_CHANNEL_PORT";
+/// Unix socket path prefix, channel id and extension are appended to it.
+#[cfg(feature = "fake")]
+pub const CHANNEL_SOCKET: &str = "/tmp/tornado";
+/// Environment variable overriding `CHANNEL_SOCKET`.
+#[cfg(feature = "fake")]
+pub const CHANNEL_SOCKET_ENV: &str = "TORNADO_CHANNEL_SOCKET";
+/// Environment variable with path to capture messages passed through fake MCU channels to.
+#[cfg(feature = "fake")]
+pub const MCU_CAPTURE_ENV: &str = "TORNADO_MCU_CAPTURE";
+/// Environment variable enabling virtual time in fake MCU, any value enables it.
+#[cfg(feature = "fake")]
+pub const VIRTUAL_TIME_ENV: &str = "TORNADO_VIRTUAL_TIME";
+
+/// Port of channel `id`. Base port may be overridden by `CHANNEL_PORT_ENV`.
+#[cfg(feature = "fake")]
+pub fn channel_port(id: u32) -> u16 {
+    let base = match env::var(CHANNEL_PORT_ENV) {
+        Ok(value) => value.parse().expect("Bad channel port"),
+        Err(_) => CHANNEL_PORT,
+    };
+    base + id as u16
+}
+
+/// Path of socket for channel `id`. Prefix may be overridden by `CHANNEL_SOCKET_ENV`.
+#[cfg(feature = "fake")]
+pub fn channel_socket(id: u32) -> String {
+    let prefix = env::var(CHANNEL_SOCKET_ENV).unwrap_or_else(|_| CHANNEL_SOCKET.into());
+    format!("{}.{}.sock", prefix, id)
+}
@@ -1,5 +1,10 @@
 #![no_std]
 
+extern crate alloc;
+
+pub mod buffer;
+pub mod capture;
 pub mod config;
 pub mod protocol;
+pub mod rates;
 pub mod values;
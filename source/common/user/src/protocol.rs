@@ -1,5 +1,5 @@
 use crate::{
-    config::{AI_COUNT, MAX_APP_MSG_LEN, MAX_MCU_MSG_LEN},
+    config::{AI_COUNT, LOOP_HIST_BINS, MAX_APP_MSG_LEN, MAX_MCU_MSG_LEN},
     values::{Di, Do, Point, Uv},
 };
 use core::mem::size_of;
@@ -11,14 +11,69 @@ use flatty::{
     FlatVec,
 };
 
+/// Maximum length of MCU task name, longer names are truncated.
+pub const TASK_NAME_LEN: usize = 16;
+
+/// Resource usage of MCU task.
+#[flat]
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub struct TaskUsage {
+    /// Task name padded with zeros.
+    pub name: [u8; TASK_NAME_LEN],
+    /// Total run time in ticks of `McuMsg::TaskStats::run_time_freq`, wraps around.
+    pub run_time: u32,
+    /// Minimum free stack space in bytes, `u32::MAX` if unknown.
+    pub stack_free: u32,
+}
+
+/// Groups of MCU statistics for `AppMsg::StatsReset::mask`.
+pub mod stats_reset {
+    use crate::config::AI_COUNT;
+
+    /// Sample and interrupt counters, SkifIO state, control loop timing and AI buffer counters.
+    pub const GLOBAL: u32 = 1 << 0;
+    /// AO buffer counters and AO value statistics.
+    pub const AO: u32 = 1 << 1;
+    /// SkifIO CRC error counter.
+    pub const CRC_ERRORS: u32 = 1 << 2;
+    /// IOC drop and stop counters.
+    pub const IOC_DROPS: u32 = 1 << 3;
+    /// Value statistics of AI channel `index`.
+    pub const fn ai(index: usize) -> u32 {
+        assert!(index < AI_COUNT);
+        1 << (8 + index)
+    }
+    /// All statistics.
+    pub const ALL: u32 = u32::MAX;
+}
+
 #[flat(sized = false, tag_type = "u8")]
 pub enum AppMsg {
-    KeepAlive,
-    DoUpdate { value: Do },
-    AoState { enable: Bool },
-    AoData { points: FlatVec<Point, u16> },
-    AoAdd { value: Uv },
-    StatsReset,
+    KeepAlive {
+        seq: u32,
+    },
+    DoUpdate {
+        value: Do,
+    },
+    AoState {
+        enable: Bool,
+    },
+    AoData {
+        points: FlatVec<Point, u16>,
+    },
+    AoAdd {
+        value: Uv,
+    },
+    /// Reset groups of MCU statistics selected by `stats_reset` bits.
+    StatsReset {
+        mask: u32,
+    },
+    AoReadbackState {
+        enable: Bool,
+    },
+    Goodbye,
+    /// First message on data channel, lets MCU know where to send bulk data.
+    Hello,
 }
 
 #[flat(sized = false, tag_type = "u8")]
@@ -39,6 +94,131 @@ pub enum McuMsg {
     Debug {
         message: FlatVec<u8, u16>,
     },
+    AoReadback {
+        points: FlatVec<Point, u16>,
+    },
+    SkifioState {
+        temp: i8,
+        status: u8,
+    },
+    KeepAliveAck {
+        seq: u32,
+    },
+    /// Full state snapshot sent on IOC connection.
+    StateSync {
+        di: Di,
+        do_: Do,
+        ao_enabled: Bool,
+        ao_add: Uv,
+    },
+    /// Control loop timing histograms since the last statistics reset.
+    ///
+    /// Bins are bounded by `config::LOOP_HIST_BOUNDS_US`.
+    LoopStats {
+        /// Time between consecutive samples.
+        cycle: [u32; LOOP_HIST_BINS],
+        /// Time spent waiting for SkifIO to become ready.
+        wait_ready: [u32; LOOP_HIST_BINS],
+        /// Maximum time between consecutive samples in microseconds.
+        cycle_max: u32,
+        /// Maximum time spent waiting for SkifIO in microseconds.
+        wait_ready_max: u32,
+    },
+    /// Resource usage of MCU tasks.
+    TaskStats {
+        /// Frequency of task run time counter in Hz.
+        run_time_freq: u32,
+        tasks: FlatVec<TaskUsage, u16>,
+    },
+    /// Event counters since the last statistics reset, used by IOC to derive rates.
+    ///
+    /// Counters wrap around.
+    StatsCounters {
+        /// Number of 10 kHz sync signals captured.
+        sync: u32,
+        /// Number of AI/AO samples.
+        samples: u32,
+        /// Number of CRC16 mismatches in SkifIO communication.
+        crc_errors: u32,
+        /// Number of AO points lost because the AO buffer was empty or full.
+        ao_lost: u32,
+        /// MCU time when counters were taken in microseconds, it is not reset with statistics.
+        time_us: u32,
+    },
+}
+
+/// Defines table of message variant names and `name`/`index` methods of message reference.
+///
+/// All variants must be listed, index of variant is its position in the table.
+macro_rules! variant_names {
+    ($names:ident, $msg_ref:ident, [$($variant:ident),* $(,)?]) => {
+        /// Names of message variants, used in logs and PV names.
+        pub const $names: [&str; [$(stringify!($variant)),*].len()] = [$(stringify!($variant)),*];
+
+        impl $msg_ref<'_> {
+            /// Name of message variant.
+            pub fn name(&self) -> &'static str {
+                $names[self.index()]
+            }
+            /// Index of message variant in names table.
+            pub fn index(&self) -> usize {
+                enum Index {
+                    $($variant),*
+                }
+                match self {
+                    $(Self::$variant { .. } => Index::$variant as usize),*
+                }
+            }
+        }
+    };
+}
+
+variant_names!(
+    APP_MSG_NAMES,
+    AppMsgRef,
+    [
+        KeepAlive,
+        DoUpdate,
+        AoState,
+        AoData,
+        AoAdd,
+        StatsReset,
+        AoReadbackState,
+        Goodbye,
+        Hello,
+    ]
+);
+
+variant_names!(
+    MCU_MSG_NAMES,
+    McuMsgRef,
+    [
+        DiUpdate,
+        AoRequest,
+        AiData,
+        Error,
+        Debug,
+        AoReadback,
+        SkifioState,
+        KeepAliveAck,
+        StateSync,
+        LoopStats,
+        TaskStats,
+        StatsCounters,
+    ]
+);
+
+/// Maximum size of serialized message of this type.
+pub trait MaxLen {
+    const MAX_LEN: usize;
+}
+
+impl MaxLen for AppMsg {
+    const MAX_LEN: usize = MAX_APP_MSG_LEN;
+}
+
+impl MaxLen for McuMsg {
+    const MAX_LEN: usize = MAX_MCU_MSG_LEN;
 }
 
 /// Calculate `AppMsg::DacData::points` capacity based on its layout.
@@ -52,3 +232,9 @@ pub const AI_MSG_MAX_POINTS: usize = (floor_mul(MAX_MCU_MSG_LEN, McuMsg::ALIGN)
     - ceil_mul(size_of::<McuMsgTag>(), McuMsg::ALIGN)
     - ceil_mul(size_of::<u16>(), Point::ALIGN))
     / (AI_COUNT * size_of::<Point>());
+
+/// Calculate `McuMsg::AoReadback::points` capacity based on its layout.
+pub const AO_READBACK_MSG_MAX_POINTS: usize = (floor_mul(MAX_MCU_MSG_LEN, McuMsg::ALIGN)
+    - ceil_mul(size_of::<McuMsgTag>(), McuMsg::ALIGN)
+    - ceil_mul(size_of::<u16>(), Point::ALIGN))
+    / size_of::<Point>();
@@ -0,0 +1,100 @@
+//! Rates of MCU event counters averaged over time windows.
+//!
+//! Rates are computed in MCU time that is sent together with counters,
+//! so they don't depend on message delivery jitter.
+
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+/// History of `N` counters covering the longest rate window.
+pub struct RateHistory<const N: usize> {
+    max_window: Duration,
+    /// MCU time of the last received counters as sent, in microseconds.
+    last_time_us: Option<u32>,
+    /// Time of the last received counters in history timeline.
+    last_time: Duration,
+    /// Counters and their times in history timeline.
+    samples: VecDeque<(Duration, [u32; N])>,
+    /// Number of samples at the back of `samples` added by `stall`.
+    stalled: usize,
+}
+
+impl<const N: usize> RateHistory<N> {
+    /// Samples older than `max_window` are dropped.
+    pub fn new(max_window: Duration) -> Self {
+        Self {
+            max_window,
+            last_time_us: None,
+            last_time: Duration::ZERO,
+            samples: VecDeque::new(),
+            stalled: 0,
+        }
+    }
+
+    /// Add counters taken at MCU time `time_us`, which wraps around.
+    pub fn push(&mut self, time_us: u32, values: [u32; N]) {
+        // Samples added while counters were late are replaced by the real ones.
+        for _ in 0..self.stalled {
+            self.samples.pop_back();
+        }
+        self.stalled = 0;
+
+        // Counters decrease when statistics are reset, MCU is restarted or a counter wraps around.
+        // Rates are computed from scratch in that case.
+        if let Some((_, last)) = self.samples.back() {
+            if values.iter().zip(last).any(|(x, y)| x < y) {
+                self.samples.clear();
+            }
+        }
+        match self.last_time_us {
+            Some(last_us) if !self.samples.is_empty() => {
+                self.last_time += Duration::from_micros(time_us.wrapping_sub(last_us) as u64)
+            }
+            _ => (),
+        }
+        self.last_time_us = Some(time_us);
+        self.samples.push_back((self.last_time, values));
+        self.trim();
+    }
+
+    /// Counters are late by `delay`, so they are considered unchanged during it.
+    pub fn stall(&mut self, delay: Duration) {
+        if let Some(&(time, values)) = self.samples.back() {
+            self.samples.push_back((time + delay, values));
+            self.stalled += 1;
+            self.trim();
+        }
+    }
+
+    /// Keep one sample older than the longest window to compute rate over the whole window.
+    fn trim(&mut self) {
+        let time = match self.samples.back() {
+            Some((time, _)) => *time,
+            None => return,
+        };
+        if let Some(start) = time.checked_sub(self.max_window) {
+            while self.samples.len() > 1 && self.samples[1].0 <= start {
+                self.samples.pop_front();
+            }
+        }
+    }
+
+    /// Rates of counters in events per second over window of `length` ending at the last sample.
+    ///
+    /// If history is shorter than `length` then rates are computed over the whole history.
+    pub fn rates(&self, length: Duration) -> Option<[f64; N]> {
+        let (time, values) = self.samples.back()?;
+        let base = match time.checked_sub(length) {
+            Some(start) => self.samples.iter().rev().find(|(t, _)| *t <= start),
+            None => None,
+        };
+        let (base_time, base_values) = base.or(self.samples.front())?;
+        let elapsed = (*time - *base_time).as_secs_f64();
+        if elapsed == 0.0 {
+            return None;
+        }
+        Some(core::array::from_fn(|i| {
+            (values[i] - base_values[i]) as f64 / elapsed
+        }))
+    }
+}
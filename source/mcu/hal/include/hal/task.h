@@ -0,0 +1,32 @@
+#pragma once
+
+#include <stdlib.h>
+#include <stdint.h>
+
+#include <FreeRTOS.h>
+
+/*!
+ * @brief Maximum task name length including terminating zero.
+ * @note Must match `HAL_TASK_NAME_LEN` in user code, which is `TASK_NAME_LEN + 1` from protocol.
+ */
+#define HAL_TASK_NAME_LEN 17
+
+_Static_assert(HAL_TASK_NAME_LEN == configMAX_TASK_NAME_LEN, "HAL_TASK_NAME_LEN mismatch with FreeRTOSConfig.h");
+
+/*! @brief Resource usage of a task. */
+typedef struct {
+    /*! @brief Zero-terminated task name. */
+    char name[HAL_TASK_NAME_LEN];
+    /*! @brief Total run time in `hal_run_time_counter` ticks, wraps around. */
+    uint32_t run_time;
+    /*! @brief Minimum amount of free stack space in bytes since task start. */
+    uint32_t stack_free;
+} HalTaskUsage;
+
+/*!
+ * @brief Get resource usage of all tasks.
+ * @param[out] usage Array to store usage to.
+ * @param[in] max_count Length of `usage` array. Tasks that don't fit are skipped.
+ * @return Number of tasks written to `usage`.
+ */
+size_t hal_task_usage(HalTaskUsage *usage, size_t max_count);
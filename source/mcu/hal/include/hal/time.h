@@ -3,3 +3,33 @@
 #include <stdint.h>
 
 uint32_t hal_busy_wait_ns(uint64_t ns);
+
+/*!
+ * @brief Enable core cycle counter.
+ */
+void hal_cycle_counter_init(void);
+
+/*!
+ * @brief Read core cycle counter.
+ * @return Number of core clock cycles, wraps around on overflow.
+ */
+uint32_t hal_cycle_count(void);
+
+/*!
+ * @brief Get core cycle counter frequency.
+ * @return Frequency in Hz.
+ */
+uint32_t hal_cycle_freq(void);
+
+/*!
+ * @brief Read task run time counter.
+ * @note Counter is derived from core cycle counter and must be read more often than the latter wraps around.
+ * @return Counter value, wraps around on overflow.
+ */
+uint32_t hal_run_time_counter(void);
+
+/*!
+ * @brief Get task run time counter frequency.
+ * @return Frequency in Hz.
+ */
+uint32_t hal_run_time_freq(void);
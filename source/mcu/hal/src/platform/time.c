@@ -0,0 +1,37 @@
+#include <stdint.h>
+
+#include <fsl_common.h>
+
+#include <hal/time.h>
+
+/*! @brief Run time counter is core cycle counter divided by `1 << RUN_TIME_SHIFT`. */
+#define RUN_TIME_SHIFT 8
+
+void hal_cycle_counter_init(void) {
+    CoreDebug->DEMCR |= CoreDebug_DEMCR_TRCENA_Msk;
+    DWT->CYCCNT = 0;
+    DWT->CTRL |= DWT_CTRL_CYCCNTENA_Msk;
+}
+
+uint32_t hal_cycle_count(void) {
+    return DWT->CYCCNT;
+}
+
+uint32_t hal_cycle_freq(void) {
+    return SystemCoreClock;
+}
+
+uint32_t hal_run_time_counter(void) {
+    static uint32_t last = 0;
+    static uint32_t wraps = 0;
+    const uint32_t cycles = DWT->CYCCNT;
+    if (cycles < last) {
+        wraps += 1;
+    }
+    last = cycles;
+    return (wraps << (32 - RUN_TIME_SHIFT)) | (cycles >> RUN_TIME_SHIFT);
+}
+
+uint32_t hal_run_time_freq(void) {
+    return SystemCoreClock >> RUN_TIME_SHIFT;
+}
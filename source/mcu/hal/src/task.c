@@ -0,0 +1,28 @@
+#include <string.h>
+
+#include <FreeRTOS.h>
+#include <task.h>
+
+#include <hal/task.h>
+
+size_t hal_task_usage(HalTaskUsage *usage, size_t max_count) {
+    const UBaseType_t task_count = uxTaskGetNumberOfTasks();
+    TaskStatus_t *status = pvPortMalloc(task_count * sizeof(TaskStatus_t));
+    if (status == NULL) {
+        return 0;
+    }
+    // Number of tasks may decrease in between, so use returned one.
+    const UBaseType_t count = uxTaskGetSystemState(status, task_count, NULL);
+
+    size_t written = 0;
+    for (UBaseType_t i = 0; i < count && written < max_count; ++i) {
+        HalTaskUsage *task = &usage[written++];
+        strncpy(task->name, status[i].pcTaskName, HAL_TASK_NAME_LEN - 1);
+        task->name[HAL_TASK_NAME_LEN - 1] = '\0';
+        task->run_time = status[i].ulRunTimeCounter;
+        task->stack_free = status[i].usStackHighWaterMark * sizeof(StackType_t);
+    }
+
+    vPortFree(status);
+    return written;
+}
@@ -45,7 +45,7 @@
 #define configTICK_RATE_HZ                      ((TickType_t)1000)
 #define configMAX_PRIORITIES                    5
 #define configMINIMAL_STACK_SIZE                ((unsigned short)256)
-#define configMAX_TASK_NAME_LEN                 10
+#define configMAX_TASK_NAME_LEN                 17
 #define configUSE_16_BIT_TICKS                  0
 #define configIDLE_SHOULD_YIELD                 1
 #define configUSE_TASK_NOTIFICATIONS            1
@@ -78,7 +78,7 @@
 #define configUSE_DAEMON_TASK_STARTUP_HOOK      0
 
 /* Run time and task stats gathering related definitions. */
-#define configGENERATE_RUN_TIME_STATS           0
+#define configGENERATE_RUN_TIME_STATS           1
 #define configUSE_TRACE_FACILITY                1
 #define configUSE_STATS_FORMATTING_FUNCTIONS    0
 
@@ -121,6 +121,12 @@
     /* Clock manager provides in this variable system core clock frequency */
     #include <stdint.h>
     extern uint32_t SystemCoreClock;
+
+    /* Task run time is measured by core cycle counter, see `hal/time.h` */
+    extern void hal_cycle_counter_init(void);
+    extern uint32_t hal_run_time_counter(void);
+    #define portCONFIGURE_TIMER_FOR_RUN_TIME_STATS() hal_cycle_counter_init()
+    #define portGET_RUN_TIME_COUNTER_VALUE() hal_run_time_counter()
 #endif
 
 /* Redefine: Mutex is needed for SRTM communication */
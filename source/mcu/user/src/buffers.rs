@@ -34,7 +34,13 @@ pub type AoConsumer = Cons<'static, Point, AO_BUFFER_LEN>;
 pub type AiProducer = Prod<'static, [Point; AI_COUNT], AI_BUFFER_LEN>;
 pub type AiConsumer = Cons<'static, [Point; AI_COUNT], AI_BUFFER_LEN>;
 
+/// AO readback is produced at the same rate as AI so it uses the same buffer length.
+pub type AoReadbackBuffer = Rb<Point, AI_BUFFER_LEN>;
+pub type AoReadbackProducer = Prod<'static, Point, AI_BUFFER_LEN>;
+pub type AoReadbackConsumer = Cons<'static, Point, AI_BUFFER_LEN>;
+
 once_mut! {
     pub static mut AO_BUFFER: Rb<Point, AO_BUFFER_LEN> = Rb::default();
     pub static mut AI_BUFFER: Rb<[Point; AI_COUNT], AI_BUFFER_LEN> = Rb::default();
+    pub static mut AO_READBACK_BUFFER: Rb<Point, AI_BUFFER_LEN> = Rb::default();
 }
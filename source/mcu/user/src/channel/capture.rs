@@ -0,0 +1,64 @@
+extern crate std;
+
+use crate::time;
+use alloc::vec::Vec;
+use common::{
+    capture::{Direction, RecordHeader, MAGIC},
+    config,
+};
+use core::time::Duration;
+use std::{
+    env,
+    fs::File,
+    io::Write,
+    sync::{Mutex, OnceLock},
+};
+use ustd::println;
+
+/// Writes messages passed through fake MCU channels to capture file.
+pub struct Recorder {
+    file: Mutex<File>,
+    /// Clock time when capture started.
+    start: Duration,
+}
+
+static RECORDER: OnceLock<Option<Recorder>> = OnceLock::new();
+
+impl Recorder {
+    fn from_env() -> Option<Self> {
+        let path = env::var_os(config::MCU_CAPTURE_ENV)?;
+        let mut file = match File::create(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                println!("Cannot create capture {:?}: {}", path, err);
+                return None;
+            }
+        };
+        file.write_all(&MAGIC).unwrap();
+        println!("Capture messages to {:?}", path);
+        Some(Self {
+            file: Mutex::new(file),
+            start: time::now(),
+        })
+    }
+
+    /// Recorder shared by all channels, `None` if capture is disabled.
+    pub fn get() -> Option<&'static Self> {
+        RECORDER.get_or_init(Self::from_env).as_ref()
+    }
+
+    pub fn record(&self, direction: Direction, channel: u32, msg: &[u8]) {
+        let header = RecordHeader {
+            time_ns: (time::now() - self.start).as_nanos() as u64,
+            direction,
+            channel: channel as u8,
+            len: msg.len() as u16,
+        };
+        let mut record = Vec::with_capacity(RecordHeader::SIZE + msg.len());
+        record.extend_from_slice(&header.to_bytes());
+        record.extend_from_slice(msg);
+        if let Err(err) = self.file.lock().unwrap().write_all(&record) {
+            println!("Cannot write capture: {}", err);
+        }
+    }
+}
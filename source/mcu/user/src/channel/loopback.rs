@@ -0,0 +1,60 @@
+extern crate std;
+
+use super::{endpoints, Listen, ReadEndpoint, WriteEndpoint};
+use crate::Error;
+use alloc::collections::BTreeMap;
+use core::time::Duration;
+use std::{
+    io,
+    os::unix::net::UnixStream,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+};
+use ustd::task::TaskContext;
+
+/// Channels waiting for connection from the same process, indexed by id.
+static LISTENERS: Mutex<BTreeMap<u32, Sender<UnixStream>>> = Mutex::new(BTreeMap::new());
+
+/// Listening channel, connections are accepted by its read half.
+pub struct Channel {
+    id: u32,
+    receiver: Receiver<UnixStream>,
+}
+impl Channel {
+    pub fn new(_cx: &TaskContext, id: u32) -> Result<Self, Error> {
+        let (sender, receiver) = channel();
+        LISTENERS.lock().unwrap().insert(id, sender);
+        Ok(Self { id, receiver })
+    }
+    pub fn split(self) -> Result<(ReadChannel, WriteChannel), Error> {
+        Ok(endpoints(self.receiver, self.id))
+    }
+}
+
+/// Connect to channel `id` of MCU running in the same process.
+pub fn connect(id: u32) -> Result<UnixStream, io::Error> {
+    let listeners = LISTENERS.lock().unwrap();
+    let listener = listeners.get(&id).ok_or(io::ErrorKind::ConnectionRefused)?;
+    let (mcu, app) = UnixStream::pair()?;
+    listener.send(mcu).map_err(|_| io::ErrorKind::ConnectionRefused)?;
+    Ok(app)
+}
+
+impl Listen for Receiver<UnixStream> {
+    type Stream = UnixStream;
+
+    fn accept(&mut self) -> io::Result<UnixStream> {
+        self.recv().map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+    fn try_clone(stream: &UnixStream) -> io::Result<UnixStream> {
+        stream.try_clone()
+    }
+    fn set_write_timeout(stream: &UnixStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_write_timeout(timeout)
+    }
+}
+
+pub type ReadChannel = ReadEndpoint<Receiver<UnixStream>>;
+pub type WriteChannel = WriteEndpoint<Receiver<UnixStream>>;
@@ -3,7 +3,24 @@ mod rpmsg;
 #[cfg(feature = "real")]
 pub use rpmsg::*;
 
-#[cfg(feature = "fake")]
+#[cfg(all(feature = "fake", not(any(feature = "unix", feature = "loopback"))))]
 mod tcp;
-#[cfg(feature = "fake")]
+#[cfg(all(feature = "fake", not(any(feature = "unix", feature = "loopback"))))]
 pub use tcp::*;
+
+#[cfg(all(feature = "unix", not(feature = "loopback")))]
+mod unix;
+#[cfg(all(feature = "unix", not(feature = "loopback")))]
+pub use unix::*;
+
+#[cfg(feature = "loopback")]
+mod loopback;
+#[cfg(feature = "loopback")]
+pub use loopback::*;
+
+#[cfg(feature = "fake")]
+mod capture;
+#[cfg(feature = "fake")]
+mod stream;
+#[cfg(feature = "fake")]
+pub use stream::*;
@@ -81,9 +81,9 @@ impl Channel {
         }
     }
 
-    pub fn split(self) -> (ReadChannel, WriteChannel) {
+    pub fn split(self) -> Result<(ReadChannel, WriteChannel), Error> {
         let this = Arc::new(self);
-        (ReadChannel(this.clone()), WriteChannel(this))
+        Ok((ReadChannel(this.clone()), WriteChannel(this)))
     }
 }
 
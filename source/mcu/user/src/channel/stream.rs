@@ -0,0 +1,310 @@
+extern crate std;
+
+use super::{capture::Recorder, ReadChannel, WriteChannel};
+use crate::{
+    error::{Error, ErrorKind, ErrorSource},
+    time,
+};
+use alloc::sync::Arc;
+use common::{buffer::Buffer, capture::Direction, protocol::MaxLen};
+use core::{
+    marker::PhantomData,
+    mem::size_of,
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+use flatty::{Emplacer, Flat, FlatDefault};
+use std::{
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+    sync::Mutex,
+};
+use timeout_readwrite::TimeoutReader;
+use ustd::println;
+
+/// Messages are sent over byte stream prefixed with their length.
+type FrameLen = u16;
+
+/// Source of connections of fake channel.
+pub trait Listen: Send + 'static {
+    type Stream: Read + Write + AsRawFd + Send + 'static;
+
+    /// Wait for a new connection.
+    fn accept(&mut self) -> io::Result<Self::Stream>;
+    fn try_clone(stream: &Self::Stream) -> io::Result<Self::Stream>;
+    fn set_write_timeout(stream: &Self::Stream, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+/// Write half of current connection, shared between endpoints of the same channel.
+type Slot<S> = Arc<Mutex<Option<S>>>;
+
+/// Read half of fake channel.
+///
+/// Connection is accepted on first read and accepted again after peer has closed it.
+pub struct ReadEndpoint<L: Listen> {
+    listener: L,
+    stream: Option<TimeoutReader<L::Stream>>,
+    slot: Slot<L::Stream>,
+    id: u32,
+}
+
+/// Write half of fake channel.
+///
+/// Messages written while there is no connection are discarded.
+pub struct WriteEndpoint<L: Listen> {
+    slot: Slot<L::Stream>,
+    id: u32,
+}
+
+/// Make endpoints of fake channel `id` accepting connections from `listener`.
+pub fn endpoints<L: Listen>(listener: L, id: u32) -> (ReadEndpoint<L>, WriteEndpoint<L>) {
+    let slot = Arc::new(Mutex::new(None));
+    (
+        ReadEndpoint {
+            listener,
+            stream: None,
+            slot: slot.clone(),
+            id,
+        },
+        WriteEndpoint { slot, id },
+    )
+}
+
+fn disconnected() -> Error {
+    Error {
+        kind: ErrorKind::Disconnected,
+        source: ErrorSource::Other("Connection closed"),
+    }
+}
+
+impl<L: Listen> ReadEndpoint<L> {
+    fn read(&mut self, dst: &mut [u8], timeout: Option<Duration>) -> Result<usize, Error> {
+        if self.stream.is_none() {
+            let stream = self.listener.accept()?;
+            *self.slot.lock().unwrap() = Some(L::try_clone(&stream)?);
+            println!("Channel {} connected", self.id);
+            // Stream timeout only limits a single attempt, the whole wait is timed by clock.
+            self.stream = Some(TimeoutReader::new(stream, time::wait_slice(timeout)));
+        }
+        let stream = self.stream.as_mut().unwrap();
+        let mut res = None;
+        time::wait(timeout, |_| match stream.read(dst) {
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => false,
+            r => {
+                res = Some(r);
+                true
+            }
+        });
+        let res = match res {
+            None => Err(io::Error::from(io::ErrorKind::TimedOut).into()),
+            Some(Ok(0)) => Err(disconnected()),
+            Some(Ok(n)) => Ok(n),
+            Some(Err(e)) => Err(Error::from(e)),
+        };
+        if let Err(Error {
+            kind: ErrorKind::Disconnected,
+            ..
+        }) = res
+        {
+            self.close();
+        }
+        res
+    }
+
+    /// Drop current connection, new one will be accepted on next read.
+    fn close(&mut self) {
+        self.stream = None;
+        self.slot.lock().unwrap().take();
+        println!("Channel {} disconnected", self.id);
+    }
+}
+
+impl<L: Listen> WriteEndpoint<L> {
+    /// Write `parts` to current connection one after another.
+    ///
+    /// Returns `false` if there is no connection.
+    fn write(&mut self, parts: &[&[u8]], timeout: Option<Duration>) -> Result<bool, Error> {
+        let mut slot = self.slot.lock().unwrap();
+        let stream = match slot.as_mut() {
+            Some(stream) => stream,
+            None => return Ok(false),
+        };
+        L::set_write_timeout(stream, timeout)?;
+        for part in parts {
+            if let Err(e) = stream.write_all(part).map_err(Error::from) {
+                if let ErrorKind::Disconnected = e.kind {
+                    // Reader will notice it and accept a new connection.
+                    slot.take();
+                    return Ok(false);
+                }
+                return Err(e);
+            }
+        }
+        Ok(true)
+    }
+}
+
+pub struct Reader<M: Flat + ?Sized> {
+    channel: ReadChannel,
+    timeout: Option<Duration>,
+    id: u32,
+    header: [u8; size_of::<FrameLen>()],
+    buffer: Buffer,
+    /// Number of bytes of current frame received so far.
+    ///
+    /// Kept between calls to resume reading after timeout.
+    pos: usize,
+    _p: PhantomData<M>,
+}
+
+pub struct Writer<M: Flat + ?Sized> {
+    channel: WriteChannel,
+    timeout: Option<Duration>,
+    id: u32,
+    buffer: Buffer,
+    _p: PhantomData<M>,
+}
+
+pub struct ReadGuard<'a, M: Flat + ?Sized> {
+    buffer: &'a [u8],
+    _p: PhantomData<M>,
+}
+
+pub struct UninitWriteGuard<'a, M: Flat + ?Sized> {
+    writer: &'a mut Writer<M>,
+}
+
+pub struct WriteGuard<'a, M: Flat + ?Sized> {
+    writer: &'a mut Writer<M>,
+}
+
+impl<M: Flat + ?Sized> Reader<M> {
+    /// `timeout` of a single read is measured by [`time`], so in virtual time mode it is virtual too.
+    pub fn new(channel: ReadChannel, timeout: Option<Duration>) -> Self
+    where
+        M: MaxLen,
+    {
+        Self {
+            id: channel.id,
+            channel,
+            timeout,
+            header: [0; size_of::<FrameLen>()],
+            buffer: Buffer::for_msg::<M>(M::MAX_LEN),
+            pos: 0,
+            _p: PhantomData,
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<usize, Error> {
+        let header_len = self.header.len();
+        while self.pos < header_len {
+            self.pos += self.channel.read(&mut self.header[self.pos..], self.timeout)?;
+        }
+        let len = FrameLen::from_le_bytes(self.header) as usize;
+        if len > self.buffer.len() {
+            // Frame boundaries are lost, so connection cannot be used anymore.
+            self.channel.close();
+            self.pos = 0;
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Message is too long").into());
+        }
+        while self.pos < header_len + len {
+            self.pos += self
+                .channel
+                .read(&mut self.buffer[(self.pos - header_len)..len], self.timeout)?;
+        }
+        self.pos = 0;
+        Ok(len)
+    }
+
+    /// Read next message.
+    ///
+    /// Returns error of kind [`ErrorKind::Disconnected`] once for each connection closed by peer.
+    pub fn read_message(&mut self) -> Result<ReadGuard<'_, M>, Error> {
+        let len = match self.read_frame() {
+            Ok(len) => len,
+            Err(e) => {
+                if let ErrorKind::Disconnected = e.kind {
+                    // Next connection starts from a new frame.
+                    self.pos = 0;
+                }
+                return Err(e);
+            }
+        };
+        let buffer = &self.buffer[..len];
+        if let Some(recorder) = Recorder::get() {
+            recorder.record(Direction::AppToMcu, self.id, buffer);
+        }
+        M::from_bytes(buffer)?;
+        Ok(ReadGuard { buffer, _p: PhantomData })
+    }
+}
+
+impl<'a, M: Flat + ?Sized> Deref for ReadGuard<'a, M> {
+    type Target = M;
+    fn deref(&self) -> &M {
+        unsafe { M::from_bytes_unchecked(self.buffer) }
+    }
+}
+
+impl<M: Flat + ?Sized> Writer<M> {
+    pub fn new(channel: WriteChannel, timeout: Option<Duration>) -> Self
+    where
+        M: MaxLen,
+    {
+        Self {
+            id: channel.id,
+            channel,
+            timeout,
+            buffer: Buffer::for_msg::<M>(M::MAX_LEN),
+            _p: PhantomData,
+        }
+    }
+
+    pub fn alloc_message(&mut self) -> Result<UninitWriteGuard<'_, M>, Error> {
+        Ok(UninitWriteGuard { writer: self })
+    }
+
+    fn write_frame(&mut self, len: usize) -> Result<(), Error> {
+        let header = FrameLen::try_from(len).unwrap().to_le_bytes();
+        // Message sent while there is no connection is lost.
+        if !self.channel.write(&[&header, &self.buffer[..len]], self.timeout)? {
+            return Ok(());
+        }
+        if let Some(recorder) = Recorder::get() {
+            recorder.record(Direction::McuToApp, self.id, &self.buffer[..len]);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, M: Flat + ?Sized> UninitWriteGuard<'a, M> {
+    pub fn new_in_place(self, emplacer: impl Emplacer<M>) -> Result<WriteGuard<'a, M>, Error> {
+        M::new_in_place(&mut self.writer.buffer, emplacer)?;
+        Ok(WriteGuard { writer: self.writer })
+    }
+}
+
+impl<'a, M: Flat + FlatDefault + ?Sized> UninitWriteGuard<'a, M> {
+    pub fn default_in_place(self) -> Result<WriteGuard<'a, M>, Error> {
+        self.new_in_place(M::default_emplacer())
+    }
+}
+
+impl<'a, M: Flat + ?Sized> Deref for WriteGuard<'a, M> {
+    type Target = M;
+    fn deref(&self) -> &M {
+        unsafe { M::from_bytes_unchecked(&self.writer.buffer) }
+    }
+}
+impl<'a, M: Flat + ?Sized> DerefMut for WriteGuard<'a, M> {
+    fn deref_mut(&mut self) -> &mut M {
+        unsafe { M::from_mut_bytes_unchecked(&mut self.writer.buffer) }
+    }
+}
+
+impl<'a, M: Flat + ?Sized> WriteGuard<'a, M> {
+    pub fn write(self) -> Result<(), Error> {
+        let size = self.size();
+        self.writer.write_frame(size)
+    }
+}
@@ -0,0 +1,67 @@
+extern crate std;
+
+use super::{endpoints, Listen, ReadEndpoint, WriteEndpoint};
+use crate::Error;
+use common::config;
+use core::time::Duration;
+use std::{
+    format, fs, io,
+    os::unix::net::{UnixListener, UnixStream},
+};
+use ustd::task::TaskContext;
+
+/// Listening channel, connections are accepted by its read half.
+pub struct Channel {
+    listener: UnixListener,
+    id: u32,
+}
+impl Channel {
+    pub fn new(_cx: &TaskContext, id: u32) -> Result<Self, Error> {
+        let listener = bind(&config::channel_socket(id))?;
+        Ok(Self { listener, id })
+    }
+    pub fn split(self) -> Result<(ReadChannel, WriteChannel), Error> {
+        Ok(endpoints(self.listener, self.id))
+    }
+}
+
+/// Bind socket at `path`.
+///
+/// Fails if another fake MCU is listening at `path`. Socket left by a previous run that nobody
+/// listens at anymore is replaced.
+fn bind(path: &str) -> io::Result<UnixListener> {
+    match UnixListener::bind(path) {
+        Err(err) if err.kind() == io::ErrorKind::AddrInUse => match UnixStream::connect(path) {
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!(
+                    "Socket {} is used by another fake MCU, set {} to use another path",
+                    path,
+                    config::CHANNEL_SOCKET_ENV
+                ),
+            )),
+            Err(_) => {
+                fs::remove_file(path)?;
+                UnixListener::bind(path)
+            }
+        },
+        res => res,
+    }
+}
+
+impl Listen for UnixListener {
+    type Stream = UnixStream;
+
+    fn accept(&mut self) -> io::Result<UnixStream> {
+        UnixListener::accept(self).map(|(stream, _)| stream)
+    }
+    fn try_clone(stream: &UnixStream) -> io::Result<UnixStream> {
+        stream.try_clone()
+    }
+    fn set_write_timeout(stream: &UnixStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_write_timeout(timeout)
+    }
+}
+
+pub type ReadChannel = ReadEndpoint<UnixListener>;
+pub type WriteChannel = WriteEndpoint<UnixListener>;
@@ -15,6 +15,8 @@ pub enum ErrorKind {
     InvalidInput,
     InvalidData,
     BadAlloc,
+    /// Peer has closed connection.
+    Disconnected,
     Other,
 }
 
@@ -88,6 +90,10 @@ impl From<io::Error> for Error {
             io::ErrorKind::TimedOut => ErrorKind::TimedOut,
             io::ErrorKind::InvalidInput => ErrorKind::InvalidInput,
             io::ErrorKind::InvalidData => ErrorKind::InvalidData,
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => ErrorKind::Disconnected,
             _ => ErrorKind::Other,
         };
         Error {
@@ -105,17 +111,3 @@ impl From<flatty::Error> for Error {
         }
     }
 }
-
-#[cfg(feature = "fake")]
-impl From<flatty_io::ReadError> for Error {
-    fn from(err: flatty_io::ReadError) -> Self {
-        match err {
-            flatty_io::ReadError::Io(e) => e.into(),
-            flatty_io::ReadError::Parse(e) => e.into(),
-            flatty_io::ReadError::Eof => Error {
-                kind: ErrorKind::InvalidData,
-                source: ErrorSource::Other("EOF"),
-            },
-        }
-    }
-}
@@ -1,3 +1,4 @@
+use common::protocol::TASK_NAME_LEN;
 use core::{ffi::c_char, time::Duration};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,3 +46,26 @@ extern "C" {
     #[allow(dead_code)]
     fn hal_retcode_str(code: RetCode) -> *const c_char;
 }
+
+extern "C" {
+    pub fn hal_cycle_counter_init();
+    pub fn hal_cycle_count() -> u32;
+    pub fn hal_cycle_freq() -> u32;
+}
+
+/// Task name length including terminating zero, `HalTaskUsage` must have the same layout as in C.
+///
+/// It is the same as `HAL_TASK_NAME_LEN` in `hal/task.h`, which is statically checked against `configMAX_TASK_NAME_LEN`.
+pub const HAL_TASK_NAME_LEN: usize = TASK_NAME_LEN + 1;
+
+#[repr(C)]
+pub struct HalTaskUsage {
+    pub name: [c_char; HAL_TASK_NAME_LEN],
+    pub run_time: u32,
+    pub stack_free: u32,
+}
+
+extern "C" {
+    pub fn hal_run_time_freq() -> u32;
+    pub fn hal_task_usage(usage: *mut HalTaskUsage, max_count: usize) -> usize;
+}
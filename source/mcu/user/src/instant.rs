@@ -0,0 +1,53 @@
+//! Timestamps for measuring short intervals.
+//!
+//! Real MCU uses core cycle counter, so intervals must be shorter than its wrap-around period
+//! (a few seconds). Fake MCU uses its clock, so intervals are measured in virtual time if it is enabled.
+
+use core::time::Duration;
+
+#[cfg(feature = "real")]
+use crate::hal::{hal_cycle_count, hal_cycle_counter_init, hal_cycle_freq};
+#[cfg(feature = "fake")]
+use crate::time;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Instant {
+    #[cfg(feature = "real")]
+    cycles: u32,
+    /// Clock time.
+    #[cfg(feature = "fake")]
+    time: Duration,
+}
+
+/// Start time source, must be called before any `Instant` is taken.
+pub fn init() {
+    #[cfg(feature = "real")]
+    unsafe {
+        hal_cycle_counter_init()
+    };
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Self {
+            #[cfg(feature = "real")]
+            cycles: unsafe { hal_cycle_count() },
+            #[cfg(feature = "fake")]
+            time: time::now(),
+        }
+    }
+
+    /// Time passed since `earlier`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        #[cfg(feature = "real")]
+        {
+            let cycles = self.cycles.wrapping_sub(earlier.cycles) as u64;
+            let freq = unsafe { hal_cycle_freq() } as u64;
+            Duration::from_nanos(cycles * 1_000_000_000 / freq)
+        }
+        #[cfg(feature = "fake")]
+        {
+            self.time.saturating_sub(earlier.time)
+        }
+    }
+}
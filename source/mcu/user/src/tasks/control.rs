@@ -1,21 +1,22 @@
 use super::stats::Statistics;
-#[cfg(feature = "fake")]
-use crate::buffers::BUFFER_TIMEOUT;
 #[cfg(feature = "real")]
 use crate::skifio::SkifioIface as _;
+#[cfg(feature = "fake")]
+use crate::{buffers::BUFFER_TIMEOUT, time};
 use crate::{
-    buffers::{AiProducer, AoConsumer},
+    buffers::{AiProducer, AoConsumer, AoReadbackProducer},
     error::{Error, ErrorKind},
+    instant::Instant,
     println,
     skifio::{self, DiHandler, XferIn, XferOut},
 };
 use alloc::{boxed::Box, sync::Arc};
 use common::{
-    config::AI_COUNT,
+    config::{self, AI_COUNT},
     values::{AtomicBits, AtomicUv, Di, Do, Point, PointOpt, Uv},
 };
 use core::{
-    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicI8, AtomicU8, AtomicUsize, Ordering},
     time::Duration,
 };
 use ringbuf::traits::*;
@@ -26,15 +27,22 @@ use ustd::{
     task::{self, BlockingContext, Context, Priority, TaskContext},
 };
 
+/// Number of samples between SkifIO state notifications.
+const SKIFIO_STATE_NOTIFY_EVERY: usize = (config::SKIFIO_STATE_PERIOD.as_micros() / config::SAMPLE_PERIOD.as_micros()) as usize;
+
 pub struct ControlHandle {
-    /// Semaphore to notify that something is ready.
+    /// Semaphore to notify that control or status message is ready.
     ready_sem: Semaphore,
+    /// Semaphore to notify that bulk data is ready.
+    data_ready_sem: Semaphore,
 
     ao_enabled: AtomicBool,
     #[cfg(feature = "fake")]
     ao_enable_sem: Semaphore,
 
     pub ao_add: AtomicUv,
+    /// Whether actual AO values should be pushed to readback buffer.
+    ao_readback_enabled: AtomicBool,
 
     di: AtomicBits,
     pub do_: AtomicBits,
@@ -44,6 +52,15 @@ pub struct ControlHandle {
     /// Discrete output has changed.
     do_changed: AtomicBool,
 
+    /// SkifIO board temperature.
+    skifio_temp: AtomicI8,
+    /// SkifIO board status.
+    skifio_status: AtomicU8,
+    /// SkifIO state should be sent to IOC.
+    skifio_state_ready: AtomicBool,
+    /// Control loop, counter and task statistics should be sent to IOC.
+    stats_ready: AtomicBool,
+
     /// Number of AO points to write until notified.
     ao_notify_every: AtomicUsize,
     /// Number of AI points to read until notified.
@@ -52,6 +69,7 @@ pub struct ControlHandle {
 
 struct ControlAo {
     buffer: AoConsumer,
+    readback: AoReadbackProducer,
     last_point: Uv,
     counter: usize,
 }
@@ -65,6 +83,8 @@ struct ControlAi {
 pub struct Control {
     ao: ControlAo,
     ai: ControlAi,
+    /// Number of samples since last SkifIO state notification.
+    skifio_state_counter: usize,
     handle: Arc<ControlHandle>,
     stats: Arc<Statistics>,
 }
@@ -73,14 +93,20 @@ impl ControlHandle {
     fn new() -> Self {
         Self {
             ready_sem: Semaphore::new().unwrap(),
+            data_ready_sem: Semaphore::new().unwrap(),
             ao_enabled: AtomicBool::new(false),
             #[cfg(feature = "fake")]
             ao_enable_sem: Semaphore::new().unwrap(),
             ao_add: AtomicUv::default(),
+            ao_readback_enabled: AtomicBool::new(false),
             di: AtomicBits::default(),
             do_: AtomicBits::default(),
             di_changed: AtomicBool::new(false),
             do_changed: AtomicBool::new(false),
+            skifio_temp: AtomicI8::new(i8::MIN),
+            skifio_status: AtomicU8::new(0),
+            skifio_state_ready: AtomicBool::new(false),
+            stats_ready: AtomicBool::new(false),
             ao_notify_every: AtomicUsize::new(0),
             ai_notify_every: AtomicUsize::new(0),
         }
@@ -94,7 +120,28 @@ impl ControlHandle {
         self.ready_sem.try_give(cx);
     }
     pub fn wait_ready(&self, cx: &mut impl BlockingContext, timeout: Option<Duration>) -> bool {
-        self.ready_sem.take(cx, timeout)
+        #[cfg(feature = "real")]
+        {
+            self.ready_sem.take(cx, timeout)
+        }
+        #[cfg(feature = "fake")]
+        {
+            time::wait(timeout, |timeout| self.ready_sem.take(cx, timeout))
+        }
+    }
+
+    pub fn notify_data(&self, cx: &mut impl Context) {
+        self.data_ready_sem.try_give(cx);
+    }
+    pub fn wait_data_ready(&self, cx: &mut impl BlockingContext, timeout: Option<Duration>) -> bool {
+        #[cfg(feature = "real")]
+        {
+            self.data_ready_sem.take(cx, timeout)
+        }
+        #[cfg(feature = "fake")]
+        {
+            time::wait(timeout, |timeout| self.data_ready_sem.take(cx, timeout))
+        }
     }
 
     pub fn set_ao_mode(&self, _cx: &mut impl Context, enabled: bool) {
@@ -105,6 +152,14 @@ impl ControlHandle {
         }
     }
 
+    pub fn ao_enabled(&self) -> bool {
+        self.ao_enabled.load(Ordering::Acquire)
+    }
+
+    pub fn set_ao_readback(&self, enabled: bool) {
+        self.ao_readback_enabled.store(enabled, Ordering::Release);
+    }
+
     fn update_di(&self, value: Di) -> bool {
         if self.di.swap(value.into(), Ordering::AcqRel) != value.into() {
             self.di_changed.fetch_or(true, Ordering::AcqRel);
@@ -113,6 +168,9 @@ impl ControlHandle {
             false
         }
     }
+    pub fn di(&self) -> Di {
+        self.di.load(Ordering::Acquire).try_into().unwrap()
+    }
     pub fn take_di(&self) -> Option<Di> {
         if self.di_changed.fetch_and(false, Ordering::AcqRel) {
             Some(self.di.load(Ordering::Acquire).try_into().unwrap())
@@ -121,6 +179,28 @@ impl ControlHandle {
         }
     }
 
+    fn update_skifio_state(&self, temp: i8, status: u8) {
+        self.skifio_temp.store(temp, Ordering::Release);
+        self.skifio_status.store(status, Ordering::Release);
+    }
+    pub fn take_skifio_state(&self) -> Option<(i8, u8)> {
+        if self.skifio_state_ready.fetch_and(false, Ordering::AcqRel) {
+            Some((
+                self.skifio_temp.load(Ordering::Acquire),
+                self.skifio_status.load(Ordering::Acquire),
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn take_stats_ready(&self) -> bool {
+        self.stats_ready.fetch_and(false, Ordering::AcqRel)
+    }
+
+    pub fn do_(&self) -> Do {
+        self.do_.load(Ordering::Acquire).try_into().unwrap()
+    }
     pub fn set_do(&self, value: Do) {
         if self.do_.swap(value.into(), Ordering::AcqRel) != value.into() {
             self.do_changed.fetch_or(true, Ordering::AcqRel);
@@ -129,12 +209,18 @@ impl ControlHandle {
 }
 
 impl Control {
-    pub fn new(ao_buf: AoConsumer, ai_buf: AiProducer, stats: Arc<Statistics>) -> (Self, Arc<ControlHandle>) {
+    pub fn new(
+        ao_buf: AoConsumer,
+        ai_buf: AiProducer,
+        ao_readback_buf: AoReadbackProducer,
+        stats: Arc<Statistics>,
+    ) -> (Self, Arc<ControlHandle>) {
         let handle = Arc::new(ControlHandle::new());
         (
             Self {
                 ao: ControlAo {
                     buffer: ao_buf,
+                    readback: ao_readback_buf,
                     last_point: Uv::default(),
                     counter: 0,
                 },
@@ -143,6 +229,7 @@ impl Control {
                     last_point: [Uv::default(); AI_COUNT],
                     counter: 0,
                 },
+                skifio_state_counter: 0,
                 handle: handle.clone(),
                 stats,
             },
@@ -168,18 +255,26 @@ impl Control {
 
         #[cfg(feature = "fake")]
         while !handle.ao_enabled.load(Ordering::Acquire) {
-            if !handle.ao_enable_sem.take(cx, BUFFER_TIMEOUT) {
+            if !time::wait(BUFFER_TIMEOUT, |timeout| handle.ao_enable_sem.take(cx, timeout)) {
                 println!("AO enable timeout");
             }
         }
 
         println!("Enter SkifIO loop");
+        // Time when previous sample became ready.
+        let mut last_ready: Option<Instant> = None;
+        // Start of previous iteration, intervals are shorter than cycle counter wrap-around period.
+        let mut last_start = Instant::now();
         loop {
             let mut ready = false;
+            let mut data_ready = false;
 
             skifio.set_ao_state(handle.ao_enabled.load(Ordering::Acquire)).unwrap();
 
             // Wait for 10 kHz sync signal
+            let wait_start = Instant::now();
+            stats.advance_time(wait_start.duration_since(last_start));
+            last_start = wait_start;
             match skifio.wait_ready(Some(Duration::from_millis(1000))) {
                 Ok(()) => (),
                 Err(Error {
@@ -187,10 +282,17 @@ impl Control {
                     ..
                 }) => {
                     println!("SkifIO timeout");
+                    // Don't count the gap as a cycle.
+                    last_ready = None;
                     continue;
                 }
                 Err(e) => panic!("{:?}", e),
             }
+            let ready_time = Instant::now();
+            stats.wait_ready_time.update(ready_time.duration_since(wait_start));
+            if let Some(last) = last_ready.replace(ready_time) {
+                stats.cycle_time.update(ready_time.duration_since(last));
+            }
 
             // Write discrete output
             if handle.do_changed.fetch_and(false, Ordering::AcqRel) {
@@ -205,8 +307,10 @@ impl Control {
             // Fetch next AO value from buffer
             let mut ao = self.ao.last_point;
             if handle.ao_enabled.load(Ordering::Acquire) {
+                // Sample before waiting, otherwise fake MCU never sees empty buffer.
+                stats.ao.update_fill(self.ao.buffer.occupied_len());
                 #[cfg(feature = "fake")]
-                while !self.ao.buffer.wait_occupied(1, BUFFER_TIMEOUT) {
+                while !time::wait(BUFFER_TIMEOUT, |timeout| self.ao.buffer.wait_occupied(1, timeout)) {
                     println!("AO buffer timeout");
                 }
 
@@ -239,12 +343,18 @@ impl Control {
 
             stats.ao.update_value(ao);
 
+            // Push actual AO value to readback buffer.
+            if handle.ao_readback_enabled.load(Ordering::Acquire) && self.ao.readback.try_push(Point::from_uv(ao)).is_err() {
+                stats.ao.report_readback_lost(1);
+            }
+
             // Transfer AO/AI values to/from SkifIO board.
             {
                 let ais = match skifio.transfer(XferOut { ao }) {
                     Ok(XferIn { ais, temp, status }) => {
                         stats.set_skifio_temp(temp);
                         stats.set_skifio_status(status);
+                        handle.update_skifio_state(temp, status);
 
                         self.ai.last_point = ais;
                         ais
@@ -263,7 +373,7 @@ impl Control {
                 // Handle AIs
                 {
                     #[cfg(feature = "fake")]
-                    while !self.ai.buffer.wait_vacant(1, BUFFER_TIMEOUT) {
+                    while !time::wait(BUFFER_TIMEOUT, |timeout| self.ai.buffer.wait_vacant(1, timeout)) {
                         println!("AI buffer timeout");
                     }
 
@@ -273,20 +383,33 @@ impl Control {
                     if self.ai.buffer.try_push(ais.map(Point::from_uv)).is_err() {
                         stats.ais.report_lost_full(1);
                     }
+                    stats.ais.update_fill(self.ai.buffer.occupied_len());
 
                     // Increment AI notification counter.
                     self.ai.counter += 1;
                     if self.ai.counter >= handle.ai_notify_every.load(Ordering::Acquire) {
                         self.ai.counter = 0;
-                        ready = true;
+                        data_ready = true;
                     }
                 }
             }
 
+            // Periodically send SkifIO state and statistics.
+            self.skifio_state_counter += 1;
+            if self.skifio_state_counter >= SKIFIO_STATE_NOTIFY_EVERY {
+                self.skifio_state_counter = 0;
+                handle.skifio_state_ready.store(true, Ordering::Release);
+                handle.stats_ready.store(true, Ordering::Release);
+                ready = true;
+            }
+
             if ready {
                 // Notify
                 handle.ready_sem.try_give(cx);
             }
+            if data_ready {
+                handle.data_ready_sem.try_give(cx);
+            }
 
             stats.report_sample();
         }
@@ -294,7 +417,7 @@ impl Control {
 
     pub fn run(self, priority: Priority) {
         task::Builder::new()
-            .name("control")
+            .name(config::CONTROL_TASK.name)
             .priority(priority)
             .spawn(move |cx| self.task_main(cx))
             .unwrap();
@@ -1,7 +1,8 @@
 pub mod control;
 pub mod rpmsg;
 pub mod stats;
+pub mod usage;
 
 pub use control::{Control, ControlHandle};
-pub use rpmsg::Rpmsg;
+pub use rpmsg::{Rpmsg, RpmsgPriorities};
 pub use stats::{Statistics, STATISTICS};
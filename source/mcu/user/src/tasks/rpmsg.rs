@@ -1,6 +1,8 @@
-use super::{control::ControlHandle, stats::Statistics};
+use super::{control::ControlHandle, stats::Statistics, usage};
+#[cfg(feature = "fake")]
+use crate::{buffers::BUFFER_TIMEOUT, time};
 use crate::{
-    buffers::{AiConsumer, AoObserver, AoProducer},
+    buffers::{AiConsumer, AoObserver, AoProducer, AoReadbackConsumer},
     channel::{Channel, Reader, Writer},
     error::{Error, ErrorKind},
 };
@@ -11,10 +13,10 @@ use common::{
     values::Point,
 };
 use core::{
-    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
     time::Duration,
 };
-use flatty::{flat_vec, prelude::NativeCast};
+use flatty::{flat_vec, portable::Bool, prelude::NativeCast};
 use ringbuf::traits::*;
 #[cfg(feature = "fake")]
 use ringbuf_blocking::traits::*;
@@ -28,6 +30,7 @@ pub struct Rpmsg {
     stats: Arc<Statistics>,
     ao_buffer: AoProducer,
     ai_buffer: AiConsumer,
+    ao_readback_buffer: AoReadbackConsumer,
     ao_observer: AoObserver,
 }
 
@@ -36,26 +39,66 @@ pub struct RpmsgCommon {
     alive: AtomicBool,
     /// Number of AO points requested from IOC.
     ao_requested: AtomicUsize,
+    /// Sequence number of the last keep-alive message received.
+    keep_alive_seq: AtomicU32,
+    /// Keep-alive acknowledgement should be sent.
+    keep_alive_ack: AtomicBool,
+    /// State snapshot should be sent to IOC.
+    state_sync: AtomicBool,
+    /// IOC has opened data channel, so bulk data can be sent.
+    data_bound: AtomicBool,
     ao_observer: AoObserver,
 }
 
-pub struct RpmsgReader {
+/// Receives control messages from IOC.
+pub struct ControlReader {
     channel: Option<Reader<AppMsg>>,
-    buffer: AoProducer,
     common: Arc<RpmsgCommon>,
     control: Arc<ControlHandle>,
     stats: Arc<Statistics>,
 }
 
-pub struct RpmsgWriter {
+/// Sends control and status messages to IOC.
+pub struct ControlWriter {
+    channel: Writer<McuMsg>,
+    common: Arc<RpmsgCommon>,
+    control: Arc<ControlHandle>,
+    stats: Arc<Statistics>,
+}
+
+/// Receives AO data from IOC.
+pub struct DataReader {
+    channel: Option<Reader<AppMsg>>,
+    buffer: AoProducer,
+    common: Arc<RpmsgCommon>,
+    stats: Arc<Statistics>,
+}
+
+/// Sends AI and AO readback data to IOC.
+pub struct DataWriter {
     channel: Writer<McuMsg>,
     buffer: AiConsumer,
+    ao_readback_buffer: AoReadbackConsumer,
     common: Arc<RpmsgCommon>,
     control: Arc<ControlHandle>,
 }
 
+/// Priorities of tasks serving RPMSG channels.
+pub struct RpmsgPriorities {
+    /// Both reading and writing of control channel.
+    pub control: Priority,
+    pub data_read: Priority,
+    pub data_write: Priority,
+}
+
 impl Rpmsg {
-    pub fn new(control: Arc<ControlHandle>, ao_buffer: AoProducer, ai_buffer: AiConsumer, stats: Arc<Statistics>) -> Self {
+    pub fn new(
+        control: Arc<ControlHandle>,
+        ao_buffer: AoProducer,
+        ai_buffer: AiConsumer,
+        ao_readback_buffer: AoReadbackConsumer,
+        stats: Arc<Statistics>,
+    ) -> Self {
         control.configure(proto::AO_MSG_MAX_POINTS, proto::AI_MSG_MAX_POINTS);
         let ao_observer = ao_buffer.observe();
         Self {
@@ -63,50 +106,90 @@ impl Rpmsg {
             stats,
             ao_buffer,
             ai_buffer,
+            ao_readback_buffer,
             ao_observer,
         }
     }
 
-    fn split(self, channel: Channel) -> (RpmsgReader, RpmsgWriter) {
+    fn split(
+        self,
+        control_channel: Channel,
+        data_channel: Channel,
+    ) -> Result<(ControlReader, ControlWriter, DataReader, DataWriter), Error> {
         let common = Arc::new(RpmsgCommon {
             alive: AtomicBool::new(false),
             ao_requested: AtomicUsize::new(0),
+            keep_alive_seq: AtomicU32::new(0),
+            keep_alive_ack: AtomicBool::new(false),
+            state_sync: AtomicBool::new(false),
+            data_bound: AtomicBool::new(false),
             ao_observer: self.ao_observer,
         });
-        let (reader, writer) = channel.split();
-        (
-            RpmsgReader {
-                channel: Some(Reader::new(reader, Some(config::KEEP_ALIVE_MAX_DELAY))),
-                buffer: self.ao_buffer,
+        let (data_reader, data_writer) = data_channel.split()?;
+        let (control_reader, control_writer) = control_channel.split()?;
+        Ok((
+            ControlReader {
+                channel: Some(Reader::new(control_reader, Some(config::KEEP_ALIVE_MAX_DELAY))),
+                common: common.clone(),
+                control: self.control.clone(),
+                stats: self.stats.clone(),
+            },
+            ControlWriter {
+                channel: Writer::new(control_writer, None),
                 common: common.clone(),
                 control: self.control.clone(),
+                stats: self.stats.clone(),
+            },
+            DataReader {
+                channel: Some(Reader::new(data_reader, None)),
+                buffer: self.ao_buffer,
+                common: common.clone(),
                 stats: self.stats,
             },
-            RpmsgWriter {
-                channel: Writer::new(writer, None),
+            DataWriter {
+                channel: Writer::new(data_writer, None),
                 buffer: self.ai_buffer,
+                ao_readback_buffer: self.ao_readback_buffer,
                 common,
                 control: self.control,
             },
-        )
+        ))
     }
 
-    pub fn run(self, read_priority: Priority, write_priority: Priority) {
+    pub fn run(self, priorities: RpmsgPriorities) {
+        let RpmsgPriorities {
+            control,
+            data_read,
+            data_write,
+        } = priorities;
         task::Builder::new()
             .name("rpmsg_init")
-            .priority(read_priority.max(write_priority))
+            .priority(control.max(data_read).max(data_write))
             .spawn(move |cx| {
-                let channel = Channel::new(cx, 0).unwrap();
-                let (reader, writer) = self.split(channel);
+                // Channels are announced in this order, so data channel is `/dev/rpmsg0` on IOC side.
+                let data_channel = Channel::new(cx, config::DATA_CHANNEL_ID).unwrap();
+                let control_channel = Channel::new(cx, config::CONTROL_CHANNEL_ID).unwrap();
+                let (control_reader, control_writer, data_reader, data_writer) =
+                    self.split(control_channel, data_channel).unwrap();
                 task::Builder::new()
-                    .name("rpmsg_read")
-                    .priority(read_priority)
-                    .spawn(move |cx| reader.task_main(cx))
+                    .name(config::RPMSG_CTRL_READ_TASK.name)
+                    .priority(control)
+                    .spawn(move |cx| control_reader.task_main(cx))
                     .unwrap();
                 task::Builder::new()
-                    .name("rpmsg_write")
-                    .priority(write_priority)
-                    .spawn(move |cx| writer.task_main(cx))
+                    .name(config::RPMSG_CTRL_WRITE_TASK.name)
+                    .priority(control)
+                    .spawn(move |cx| control_writer.task_main(cx))
+                    .unwrap();
+                task::Builder::new()
+                    .name(config::RPMSG_DATA_READ_TASK.name)
+                    .priority(data_read)
+                    .spawn(move |cx| data_reader.task_main(cx))
+                    .unwrap();
+                task::Builder::new()
+                    .name(config::RPMSG_DATA_WRITE_TASK.name)
+                    .priority(data_write)
+                    .spawn(move |cx| data_writer.task_main(cx))
                     .unwrap();
             })
             .unwrap();
@@ -117,9 +200,20 @@ impl RpmsgCommon {
     fn is_alive(&self) -> bool {
         self.alive.load(Ordering::Acquire)
     }
+    fn request_keep_alive_ack(&self, seq: u32) {
+        self.keep_alive_seq.store(seq, Ordering::Release);
+        self.keep_alive_ack.store(true, Ordering::Release);
+    }
+    fn take_keep_alive_ack(&self) -> Option<u32> {
+        if self.keep_alive_ack.swap(false, Ordering::AcqRel) {
+            Some(self.keep_alive_seq.load(Ordering::Acquire))
+        } else {
+            None
+        }
+    }
 }
 
-impl RpmsgReader {
+impl ControlReader {
     fn task_main(mut self, cx: &mut TaskContext) -> ! {
         let mut channel = self.channel.take().unwrap();
         loop {
@@ -132,18 +226,48 @@ impl RpmsgReader {
                     if self.common.is_alive() {
                         println!("Keep-alive timeout reached. RPMSG connection is considered to be dead.");
                         self.disconnect(cx);
+                        self.stats.report_ioc_drop();
+                    }
+                    continue;
+                }
+                Err(Error {
+                    kind: ErrorKind::Disconnected,
+                    ..
+                }) => {
+                    if self.common.is_alive() {
+                        println!("Control channel closed by IOC");
+                        self.disconnect(cx);
+                        self.stats.report_ioc_drop();
                     }
                     continue;
                 }
+                Err(Error {
+                    kind: ErrorKind::InvalidData,
+                    source,
+                }) => {
+                    // Malformed message from IOC must not bring MCU down.
+                    println!("Error: Bad message: {:?}", source);
+                    continue;
+                }
                 Err(e) => panic!("{:?}", e),
             };
 
             use proto::AppMsgRef;
             match message.as_ref() {
-                AppMsgRef::KeepAlive => {
+                AppMsgRef::KeepAlive { seq } => {
                     if !self.common.is_alive() {
                         self.connect(cx);
                     }
+                    self.common.request_keep_alive_ack(*seq);
+                    self.control.notify(cx);
+                    continue;
+                }
+                AppMsgRef::Goodbye => {
+                    if self.common.is_alive() {
+                        println!("IOC is stopping");
+                        self.disconnect(cx);
+                        self.stats.report_ioc_stop();
+                    }
                     continue;
                 }
                 _ => {
@@ -153,7 +277,8 @@ impl RpmsgReader {
                 }
             }
             match message.as_ref() {
-                AppMsgRef::KeepAlive => unreachable!(),
+                AppMsgRef::KeepAlive { .. } | AppMsgRef::Goodbye => unreachable!(),
+                AppMsgRef::AoData { .. } | AppMsgRef::Hello => println!("Error: Unexpected message on control channel"),
                 AppMsgRef::DoUpdate { value } => {
                     // println!("Set Do: {:?}", value);
                     self.control.set_do(*value)
@@ -162,11 +287,14 @@ impl RpmsgReader {
                     println!("Set AO state: {:?}", enable);
                     self.control.set_ao_mode(cx, enable.to_native());
                 }
-                AppMsgRef::AoData { points } => self.write_ao(points),
                 AppMsgRef::AoAdd { value } => self.control.ao_add.store(*value, Ordering::Release),
-                AppMsgRef::StatsReset => {
-                    println!("Reset stats");
-                    self.stats.reset();
+                AppMsgRef::StatsReset { mask } => {
+                    println!("Reset stats: 0x{:08x}", *mask);
+                    self.stats.reset_masked(*mask);
+                }
+                AppMsgRef::AoReadbackState { enable } => {
+                    println!("Set AO readback state: {:?}", enable);
+                    self.control.set_ao_readback(enable.to_native());
                 }
             }
         }
@@ -175,6 +303,7 @@ impl RpmsgReader {
     fn connect(&mut self, cx: &mut impl Context) {
         self.common.ao_requested.store(0, Ordering::Release);
         self.control.set_ao_mode(cx, true);
+        self.common.state_sync.store(true, Ordering::Release);
         self.common.alive.store(true, Ordering::Release);
         self.control.notify(cx);
         println!("IOC connected");
@@ -183,15 +312,58 @@ impl RpmsgReader {
     fn disconnect(&mut self, cx: &mut impl Context) {
         self.common.alive.store(false, Ordering::Release);
         self.control.set_ao_mode(cx, false);
-        self.stats.report_ioc_drop();
         println!("IOC disconnected");
     }
+}
+
+impl DataReader {
+    fn task_main(mut self, _cx: &mut TaskContext) -> ! {
+        let mut channel = self.channel.take().unwrap();
+        loop {
+            let message = match channel.read_message().map_err(Error::from) {
+                Ok(msg) => msg,
+                Err(Error {
+                    kind: ErrorKind::Disconnected,
+                    ..
+                }) => {
+                    // New connection must say hello again.
+                    if self.common.data_bound.swap(false, Ordering::AcqRel) {
+                        println!("Data channel closed");
+                    }
+                    continue;
+                }
+                Err(Error {
+                    kind: ErrorKind::InvalidData,
+                    source,
+                }) => {
+                    // Malformed message from IOC must not bring MCU down.
+                    println!("Error: Bad message: {:?}", source);
+                    continue;
+                }
+                Err(e) => panic!("{:?}", e),
+            };
+
+            use proto::AppMsgRef;
+            match message.as_ref() {
+                AppMsgRef::Hello => {
+                    if !self.common.data_bound.swap(true, Ordering::AcqRel) {
+                        println!("Data channel opened");
+                    }
+                }
+                AppMsgRef::AoData { points } => self.write_ao(points),
+                _ => println!("Error: Unexpected message on data channel"),
+            }
+        }
+    }
 
     fn write_ao(&mut self, points: &[Point]) {
         // Push received points to ring buffer.
         {
             #[cfg(feature = "fake")]
-            assert!(self.buffer.wait_vacant(points.len(), crate::buffers::BUFFER_TIMEOUT));
+            {
+                let len = points.len();
+                assert!(time::wait(BUFFER_TIMEOUT, |timeout| self.buffer.wait_vacant(len, timeout)));
+            }
 
             let count = self.buffer.push_iter(&mut points.iter().copied());
             if points.len() > count {
@@ -229,24 +401,52 @@ macro_rules! try_timeout {
     };
 }
 
-impl RpmsgWriter {
+impl ControlWriter {
     fn task_main(mut self, cx: &mut TaskContext) {
         loop {
             if !self.control.wait_ready(cx, Some(Duration::from_secs(10))) {
-                println!("RPMSG send task timed out");
+                println!("RPMSG control send task timed out");
                 continue;
             }
 
             if self.common.is_alive() {
+                self.send_keep_alive_ack(cx);
+                self.send_state_sync(cx);
                 self.send_di(cx);
-                self.send_ais(cx);
+                self.send_skifio_state(cx);
+                self.send_stats(cx);
                 self.send_ao_request(cx);
-            } else {
-                self.discard_ais();
             }
         }
     }
 
+    fn send_keep_alive_ack(&mut self, _cx: &mut impl BlockingContext) {
+        if let Some(seq) = self.common.take_keep_alive_ack() {
+            try_timeout!(self.channel.alloc_message(), ())
+                .unwrap()
+                .new_in_place(proto::McuMsgInitKeepAliveAck { seq })
+                .unwrap()
+                .write()
+                .unwrap();
+        }
+    }
+
+    fn send_state_sync(&mut self, _cx: &mut impl BlockingContext) {
+        if self.common.state_sync.swap(false, Ordering::AcqRel) {
+            try_timeout!(self.channel.alloc_message(), ())
+                .unwrap()
+                .new_in_place(proto::McuMsgInitStateSync {
+                    di: self.control.di(),
+                    do_: self.control.do_(),
+                    ao_enabled: Bool::from_native(self.control.ao_enabled()),
+                    ao_add: self.control.ao_add.load(Ordering::Acquire),
+                })
+                .unwrap()
+                .write()
+                .unwrap();
+        }
+    }
+
     fn send_di(&mut self, _cx: &mut impl BlockingContext) {
         if let Some(value) = self.control.take_di() {
             try_timeout!(self.channel.alloc_message(), ())
@@ -258,29 +458,59 @@ impl RpmsgWriter {
         }
     }
 
-    fn send_ais(&mut self, _cx: &mut impl BlockingContext) -> usize {
-        let mut total = 0;
-        const LEN: usize = proto::AI_MSG_MAX_POINTS;
+    fn send_skifio_state(&mut self, _cx: &mut impl BlockingContext) {
+        if let Some((temp, status)) = self.control.take_skifio_state() {
+            try_timeout!(self.channel.alloc_message(), ())
+                .unwrap()
+                .new_in_place(proto::McuMsgInitSkifioState { temp, status })
+                .unwrap()
+                .write()
+                .unwrap();
+        }
+    }
 
-        while self.buffer.occupied_len() >= LEN {
-            let mut msg = try_timeout!(self.channel.alloc_message(), total)
+    fn send_stats(&mut self, _cx: &mut impl BlockingContext) {
+        if self.control.take_stats_ready() {
+            try_timeout!(self.channel.alloc_message(), ())
                 .unwrap()
-                .new_in_place(proto::McuMsgInitAiData { points: flat_vec![] })
+                .new_in_place(proto::McuMsgInitLoopStats {
+                    cycle: self.stats.cycle_time.counts(),
+                    wait_ready: self.stats.wait_ready_time.counts(),
+                    cycle_max: self.stats.cycle_time.max(),
+                    wait_ready_max: self.stats.wait_ready_time.max(),
+                })
+                .unwrap()
+                .write()
                 .unwrap();
 
-            let count = if let proto::McuMsgMut::AiData { points } = msg.as_mut() {
-                assert_eq!(points.capacity(), LEN);
-                points.extend_from_iter(self.buffer.pop_iter());
-                points.len()
+            try_timeout!(self.channel.alloc_message(), ())
+                .unwrap()
+                .new_in_place(proto::McuMsgInitStatsCounters {
+                    sync: self.stats.sync_count() as u32,
+                    samples: self.stats.sample_count() as u32,
+                    crc_errors: self.stats.crc_error_count() as u32,
+                    ao_lost: self.stats.ao.lost_count() as u32,
+                    time_us: self.stats.time_us(),
+                })
+                .unwrap()
+                .write()
+                .unwrap();
+
+            let mut msg = try_timeout!(self.channel.alloc_message(), ())
+                .unwrap()
+                .new_in_place(proto::McuMsgInitTaskStats {
+                    run_time_freq: usage::run_time_freq(),
+                    tasks: flat_vec![],
+                })
+                .unwrap();
+            if let proto::McuMsgMut::TaskStats { tasks, .. } = msg.as_mut() {
+                // Tasks that don't fit into message are skipped.
+                tasks.extend_from_iter(usage::task_usage());
             } else {
                 unreachable!()
-            };
-
-            assert_eq!(count, LEN);
+            }
             msg.write().unwrap();
-            total += count;
         }
-        total
     }
 
     fn send_ao_request(&mut self, _cx: &mut impl BlockingContext) {
@@ -303,10 +533,85 @@ impl RpmsgWriter {
                 .unwrap();
         }
     }
+}
+
+impl DataWriter {
+    fn task_main(mut self, cx: &mut TaskContext) {
+        loop {
+            if !self.control.wait_data_ready(cx, Some(Duration::from_secs(10))) {
+                println!("RPMSG data send task timed out");
+                continue;
+            }
+
+            if self.common.is_alive() && self.common.data_bound.load(Ordering::Acquire) {
+                self.send_ais(cx);
+                self.send_ao_readback(cx);
+            } else {
+                self.discard_ais();
+                self.discard_ao_readback();
+            }
+        }
+    }
+
+    fn send_ais(&mut self, _cx: &mut impl BlockingContext) -> usize {
+        let mut total = 0;
+        const LEN: usize = proto::AI_MSG_MAX_POINTS;
+
+        while self.buffer.occupied_len() >= LEN {
+            let mut msg = try_timeout!(self.channel.alloc_message(), total)
+                .unwrap()
+                .new_in_place(proto::McuMsgInitAiData { points: flat_vec![] })
+                .unwrap();
+
+            let count = if let proto::McuMsgMut::AiData { points } = msg.as_mut() {
+                assert_eq!(points.capacity(), LEN);
+                points.extend_from_iter(self.buffer.pop_iter());
+                points.len()
+            } else {
+                unreachable!()
+            };
+
+            assert_eq!(count, LEN);
+            msg.write().unwrap();
+            total += count;
+        }
+        total
+    }
+
+    fn send_ao_readback(&mut self, _cx: &mut impl BlockingContext) -> usize {
+        let mut total = 0;
+        const LEN: usize = proto::AO_READBACK_MSG_MAX_POINTS;
+
+        while self.ao_readback_buffer.occupied_len() >= LEN {
+            let mut msg = try_timeout!(self.channel.alloc_message(), total)
+                .unwrap()
+                .new_in_place(proto::McuMsgInitAoReadback { points: flat_vec![] })
+                .unwrap();
+
+            let count = if let proto::McuMsgMut::AoReadback { points } = msg.as_mut() {
+                assert_eq!(points.capacity(), LEN);
+                points.extend_from_iter(self.ao_readback_buffer.pop_iter());
+                points.len()
+            } else {
+                unreachable!()
+            };
+
+            assert_eq!(count, LEN);
+            msg.write().unwrap();
+            total += count;
+        }
+        total
+    }
 
     fn discard_ais(&mut self) {
         const LEN: usize = proto::AI_MSG_MAX_POINTS;
         let len = self.buffer.occupied_len();
         self.buffer.skip((len / LEN) * LEN);
     }
+
+    fn discard_ao_readback(&mut self) {
+        const LEN: usize = proto::AO_READBACK_MSG_MAX_POINTS;
+        let len = self.ao_readback_buffer.occupied_len();
+        self.ao_readback_buffer.skip((len / LEN) * LEN);
+    }
 }
@@ -1,12 +1,17 @@
-use crate::println;
+use super::usage;
+use crate::{
+    buffers::{AI_BUFFER_LEN, AO_BUFFER_LEN},
+    println,
+};
 use alloc::sync::Arc;
 use common::{
-    config::AI_COUNT,
+    config::{self, AI_COUNT, LOOP_HIST_BINS, LOOP_HIST_BOUNDS_US},
+    protocol::{stats_reset, TaskUsage},
     values::{AtomicUv, Uv},
 };
 use core::{
     fmt::{self, Display, Formatter, Write},
-    sync::atomic::{AtomicI8, AtomicU8, AtomicUsize, Ordering},
+    sync::atomic::{AtomicI8, AtomicU32, AtomicU8, AtomicUsize, Ordering},
     time::Duration,
 };
 use indenter::indented;
@@ -40,13 +45,22 @@ pub struct Statistics {
     max_intrs_per_sample: AtomicUsize,
     /// Count of CRC16 mismatches in SkifIO communication.
     crc_error_count: AtomicUsize,
+    /// Time measured by control loop in microseconds, wraps around and is not reset.
+    time_us: AtomicU32,
     /// Count of IOC being disconnected
     ioc_drop_count: AtomicUsize,
+    /// Count of IOC being stopped in an orderly way.
+    ioc_stop_count: AtomicUsize,
     /// SkifIO controller temperature.
     skifio_temp: AtomicI8,
     /// SkifIO board status.
     skifio_status: AtomicU8,
 
+    /// Time between consecutive samples.
+    pub cycle_time: Histogram,
+    /// Time spent waiting for SkifIO to become ready.
+    pub wait_ready_time: Histogram,
+
     pub ao: StatsAo,
     pub ais: StatsAis,
 }
@@ -59,6 +73,10 @@ pub struct StatsAo {
     lost_full: AtomicUsize,
     /// IOC sent more points than were requested.
     req_exceed: AtomicUsize,
+    /// Number of readback points lost because the readback buffer was full.
+    readback_lost: AtomicUsize,
+    /// Minimum number of points in the AO buffer, low watermark.
+    min_fill: AtomicUsize,
 
     value: ValueStats,
 }
@@ -67,10 +85,20 @@ pub struct StatsAo {
 pub struct StatsAis {
     /// Number of points lost because the AI buffer was full.
     lost_full: AtomicUsize,
+    /// Maximum number of points in the AI buffer, high watermark.
+    max_fill: AtomicUsize,
 
     values: [ValueStats; AI_COUNT],
 }
 
+/// Distribution of time intervals over bins bounded by `LOOP_HIST_BOUNDS_US`.
+#[derive(Default)]
+pub struct Histogram {
+    counts: [AtomicU32; LOOP_HIST_BINS],
+    /// Maximum interval in microseconds.
+    max: AtomicU32,
+}
+
 #[derive(Default)]
 pub struct ValueStats {
     count: AtomicUsize,
@@ -86,17 +114,37 @@ impl Statistics {
         this
     }
     pub fn reset(&self) {
-        self.sync_count.store(0, Ordering::Relaxed);
-        self.ready_count.store(0, Ordering::Relaxed);
-        self.sample_count.store(0, Ordering::Relaxed);
-        self.max_intrs_per_sample.store(0, Ordering::Relaxed);
-        self.crc_error_count.store(0, Ordering::Relaxed);
-        self.ioc_drop_count.store(0, Ordering::Relaxed);
-        self.skifio_temp.store(i8::MIN, Ordering::Relaxed);
-        self.skifio_status.store(0, Ordering::Relaxed);
-
-        self.ao.reset();
-        self.ais.reset();
+        self.reset_masked(stats_reset::ALL);
+    }
+    /// Reset only groups of statistics selected by `stats_reset` bits in `mask`.
+    pub fn reset_masked(&self, mask: u32) {
+        if mask & stats_reset::GLOBAL != 0 {
+            self.sync_count.store(0, Ordering::Relaxed);
+            self.ready_count.store(0, Ordering::Relaxed);
+            self.sample_count.store(0, Ordering::Relaxed);
+            self.max_intrs_per_sample.store(0, Ordering::Relaxed);
+            self.skifio_temp.store(i8::MIN, Ordering::Relaxed);
+            self.skifio_status.store(0, Ordering::Relaxed);
+
+            self.cycle_time.reset();
+            self.wait_ready_time.reset();
+            self.ais.reset_buffer();
+        }
+        if mask & stats_reset::CRC_ERRORS != 0 {
+            self.crc_error_count.store(0, Ordering::Relaxed);
+        }
+        if mask & stats_reset::IOC_DROPS != 0 {
+            self.ioc_drop_count.store(0, Ordering::Relaxed);
+            self.ioc_stop_count.store(0, Ordering::Relaxed);
+        }
+        if mask & stats_reset::AO != 0 {
+            self.ao.reset();
+        }
+        for (i, values) in self.ais.values.iter().enumerate() {
+            if mask & stats_reset::ai(i) != 0 {
+                values.reset();
+            }
+        }
     }
 
     fn intr_clock(&self) {
@@ -119,6 +167,27 @@ impl Statistics {
     pub fn report_ioc_drop(&self) {
         self.ioc_drop_count.fetch_add(1, Ordering::Relaxed);
     }
+    pub fn report_ioc_stop(&self) {
+        self.ioc_stop_count.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn ioc_drop_count(&self) -> usize {
+        self.ioc_drop_count.load(Ordering::Relaxed)
+    }
+    pub fn sync_count(&self) -> usize {
+        self.sync_count.load(Ordering::Relaxed)
+    }
+    pub fn sample_count(&self) -> usize {
+        self.sample_count.load(Ordering::Relaxed)
+    }
+    pub fn crc_error_count(&self) -> usize {
+        self.crc_error_count.load(Ordering::Relaxed)
+    }
+    pub fn advance_time(&self, dt: Duration) {
+        self.time_us.fetch_add(dt.as_micros() as u32, Ordering::Relaxed);
+    }
+    pub fn time_us(&self) -> u32 {
+        self.time_us.load(Ordering::Relaxed)
+    }
     pub fn set_skifio_temp(&self, temp: i8) {
         self.skifio_temp.store(temp, Ordering::Relaxed);
     }
@@ -137,6 +206,8 @@ impl StatsAo {
         self.lost_empty.store(0, Ordering::Relaxed);
         self.lost_full.store(0, Ordering::Relaxed);
         self.req_exceed.store(0, Ordering::Relaxed);
+        self.readback_lost.store(0, Ordering::Relaxed);
+        self.min_fill.store(usize::MAX, Ordering::Relaxed);
 
         self.value.reset();
     }
@@ -156,6 +227,16 @@ impl StatsAo {
         #[cfg(feature = "fake")]
         panic!("IOC sent more points than have been requested");
     }
+    pub fn report_readback_lost(&self, count: usize) {
+        self.readback_lost.fetch_add(count, Ordering::Relaxed);
+    }
+    pub fn update_fill(&self, len: usize) {
+        self.min_fill.fetch_min(len, Ordering::Relaxed);
+    }
+    /// Number of points lost because the AO buffer was empty or full.
+    pub fn lost_count(&self) -> usize {
+        self.lost_empty.load(Ordering::Relaxed) + self.lost_full.load(Ordering::Relaxed)
+    }
     pub fn update_value(&self, value: Uv) {
         self.value.update(value);
     }
@@ -168,15 +249,23 @@ impl StatsAis {
         this
     }
     pub fn reset(&self) {
-        self.lost_full.store(0, Ordering::Relaxed);
+        self.reset_buffer();
         self.values.iter().for_each(ValueStats::reset);
     }
+    /// Reset AI buffer counters keeping value statistics.
+    pub fn reset_buffer(&self) {
+        self.lost_full.store(0, Ordering::Relaxed);
+        self.max_fill.store(0, Ordering::Relaxed);
+    }
 
     pub fn report_lost_full(&self, count: usize) {
         self.lost_full.fetch_add(count, Ordering::Relaxed);
         #[cfg(feature = "fake")]
         panic!("AI ring buffer is full");
     }
+    pub fn update_fill(&self, len: usize) {
+        self.max_fill.fetch_max(len, Ordering::Relaxed);
+    }
     pub fn update_values(&self, values: [Uv; AI_COUNT]) {
         self.values.iter().zip(values).for_each(|(v, x)| v.update(x));
     }
@@ -202,6 +291,25 @@ impl ValueStats {
     }
 }
 
+impl Histogram {
+    pub fn reset(&self) {
+        self.counts.iter().for_each(|c| c.store(0, Ordering::Relaxed));
+        self.max.store(0, Ordering::Relaxed);
+    }
+    pub fn update(&self, time: Duration) {
+        let us = u32::try_from(time.as_micros()).unwrap_or(u32::MAX);
+        let bin = LOOP_HIST_BOUNDS_US.partition_point(|&bound| bound <= us);
+        self.counts[bin].fetch_add(1, Ordering::Relaxed);
+        self.max.fetch_max(us, Ordering::Relaxed);
+    }
+    pub fn counts(&self) -> [u32; LOOP_HIST_BINS] {
+        core::array::from_fn(|i| self.counts[i].load(Ordering::Relaxed))
+    }
+    pub fn max(&self) -> u32 {
+        self.max.load(Ordering::Relaxed)
+    }
+}
+
 impl Display for Statistics {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let sync = self.sync_count.load(Ordering::Relaxed);
@@ -218,9 +326,16 @@ impl Display for Statistics {
         )?;
         writeln!(f, "crc_error_count: {}", self.crc_error_count.load(Ordering::Relaxed))?;
         writeln!(f, "ioc_drop_count: {}", self.ioc_drop_count.load(Ordering::Relaxed))?;
+        writeln!(f, "ioc_stop_count: {}", self.ioc_stop_count.load(Ordering::Relaxed))?;
         writeln!(f, "skifio_temp: {}", self.skifio_temp.load(Ordering::Relaxed))?;
         writeln!(f, "skifio_status: 0b{:08b}", self.skifio_status.load(Ordering::Relaxed))?;
 
+        writeln!(f, "cycle_time:")?;
+        write!(indented(f), "{}", self.cycle_time)?;
+
+        writeln!(f, "wait_ready_time:")?;
+        write!(indented(f), "{}", self.wait_ready_time)?;
+
         writeln!(f, "ao:")?;
         write!(indented(f), "{}", self.ao)?;
 
@@ -236,6 +351,11 @@ impl Display for StatsAo {
         writeln!(f, "lost_empty: {}", self.lost_empty.load(Ordering::Relaxed))?;
         writeln!(f, "lost_full: {}", self.lost_full.load(Ordering::Relaxed))?;
         writeln!(f, "req_exceed: {}", self.req_exceed.load(Ordering::Relaxed))?;
+        writeln!(f, "readback_lost: {}", self.readback_lost.load(Ordering::Relaxed))?;
+        match self.min_fill.load(Ordering::Relaxed) {
+            usize::MAX => writeln!(f, "min_fill: none")?,
+            fill => writeln!(f, "min_fill: {}/{}", fill, AO_BUFFER_LEN)?,
+        }
 
         writeln!(f, "value:")?;
         write!(indented(f), "{}", self.value)?;
@@ -247,6 +367,7 @@ impl Display for StatsAo {
 impl Display for StatsAis {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(f, "lost_full: {}", self.lost_full.load(Ordering::Relaxed))?;
+        writeln!(f, "max_fill: {}/{}", self.max_fill.load(Ordering::Relaxed), AI_BUFFER_LEN)?;
 
         for (i, ai) in self.values.iter().enumerate() {
             writeln!(f, "{}:", i)?;
@@ -256,6 +377,29 @@ impl Display for StatsAis {
     }
 }
 
+impl Display for Histogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let counts = self.counts();
+        if counts.iter().all(|&c| c == 0) {
+            return writeln!(f, "empty");
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            match i {
+                0 => write!(f, "[0, {})", LOOP_HIST_BOUNDS_US[0])?,
+                _ if i == LOOP_HIST_BOUNDS_US.len() => write!(f, "[{}, inf)", LOOP_HIST_BOUNDS_US[i - 1])?,
+                _ => write!(f, "[{}, {})", LOOP_HIST_BOUNDS_US[i - 1], LOOP_HIST_BOUNDS_US[i])?,
+            }
+            writeln!(f, " us: {}", count)?;
+        }
+        writeln!(f, "max: {} us", self.max())?;
+
+        Ok(())
+    }
+}
+
 impl Display for ValueStats {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let count = self.count.load(Ordering::Relaxed);
@@ -270,16 +414,61 @@ impl Display for ValueStats {
     }
 }
 
+/// Task resource usage over the period between two samples.
+struct TasksReport<'a> {
+    tasks: &'a [TaskUsage],
+    last: &'a [TaskUsage],
+    period: Duration,
+}
+
+impl<'a> Display for TasksReport<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ticks = self.period.as_secs_f64() * usage::run_time_freq() as f64;
+        for task in self.tasks {
+            let name = core::str::from_utf8(&task.name).unwrap_or("?").trim_end_matches('\0');
+            write!(f, "{}: ", name)?;
+            match self.last.iter().find(|last| last.name == task.name) {
+                Some(last) => {
+                    let load = task.run_time.wrapping_sub(last.run_time) as f64 / ticks;
+                    write!(f, "cpu {:.1}%", 100.0 * load)?
+                }
+                None => write!(f, "cpu ?")?,
+            }
+            match task.stack_free {
+                u32::MAX => writeln!(f)?,
+                stack_free => writeln!(f, ", stack_free {}", stack_free)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Statistics {
     pub fn run_printer(self: Arc<Self>, period: Duration) {
         task::Builder::new()
-            .name("stats")
+            .name(config::STATS_TASK.name)
             .priority(1)
-            .spawn(move |cx| loop {
-                cx.sleep(Some(period));
-                println!();
-                println!("[Statistics]");
-                println!("{}", self);
+            .spawn(move |cx| {
+                let mut last = usage::task_usage();
+                loop {
+                    cx.sleep(Some(period));
+                    println!();
+                    println!("[Statistics]");
+                    println!("{}", self);
+
+                    let tasks = usage::task_usage();
+                    println!("tasks:");
+                    println!(
+                        "{}",
+                        TasksReport {
+                            tasks: &tasks,
+                            last: &last,
+                            period,
+                        }
+                    );
+                    last = tasks;
+                }
             })
             .unwrap();
     }
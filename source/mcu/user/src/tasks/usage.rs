@@ -0,0 +1,99 @@
+//! CPU time and stack usage of MCU tasks.
+
+#[cfg(feature = "fake")]
+extern crate std;
+
+use alloc::vec::Vec;
+#[cfg(feature = "fake")]
+use common::config;
+use common::protocol::{TaskUsage, TASK_NAME_LEN};
+
+#[cfg(feature = "real")]
+use crate::hal::{hal_run_time_freq, hal_task_usage, HalTaskUsage};
+
+/// Maximum number of tasks to report.
+#[cfg(feature = "real")]
+const MAX_TASKS: usize = 16;
+
+/// Pad task name with zeros or truncate it.
+fn task_name(name: impl IntoIterator<Item = u8>) -> [u8; TASK_NAME_LEN] {
+    let mut buf = [0; TASK_NAME_LEN];
+    buf.iter_mut().zip(name).for_each(|(b, c)| *b = c);
+    buf
+}
+
+/// Frequency of `TaskUsage::run_time` in Hz.
+pub fn run_time_freq() -> u32 {
+    #[cfg(feature = "real")]
+    {
+        unsafe { hal_run_time_freq() }
+    }
+    #[cfg(feature = "fake")]
+    {
+        1_000_000
+    }
+}
+
+/// Resource usage of all tasks.
+#[cfg(feature = "real")]
+pub fn task_usage() -> Vec<TaskUsage> {
+    let mut raw = Vec::<HalTaskUsage>::with_capacity(MAX_TASKS);
+    unsafe {
+        let count = hal_task_usage(raw.as_mut_ptr(), MAX_TASKS);
+        raw.set_len(count);
+    }
+    raw.iter()
+        .map(|task| TaskUsage {
+            name: task_name(task.name.iter().map(|&c| c as u8).take_while(|&c| c != 0)),
+            run_time: task.run_time,
+            stack_free: task.stack_free,
+        })
+        .collect()
+}
+
+/// Resource usage of MCU task threads, stack usage is unknown.
+///
+/// Only threads of MCU tasks are reported, statistics printer is not spawned by fake MCU.
+/// Thread CPU time is read from `/proc/self/task`.
+#[cfg(feature = "fake")]
+pub fn task_usage() -> Vec<TaskUsage> {
+    use std::fs;
+
+    let mut tasks = Vec::new();
+    let threads = match fs::read_dir("/proc/self/task") {
+        Ok(threads) => threads,
+        Err(_) => return tasks,
+    };
+    for thread in threads.flatten() {
+        let path = thread.path();
+        let (comm, schedstat) = match (
+            fs::read_to_string(path.join("comm")),
+            fs::read_to_string(path.join("schedstat")),
+        ) {
+            (Ok(comm), Ok(schedstat)) => (comm, schedstat),
+            _ => continue,
+        };
+        // Thread names are truncated by kernel to 15 bytes.
+        let comm = comm.trim_end();
+        let name = match config::MCU_TASKS
+            .iter()
+            .map(|task| task.name)
+            .filter(|&name| name != config::STATS_TASK.name)
+            .find(|&name| name.get(..15).unwrap_or(name) == comm)
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        // The first field is CPU time in nanoseconds.
+        let run_time_ns = match schedstat.split_whitespace().next().map(str::parse::<u64>) {
+            Some(Ok(ns)) => ns,
+            _ => continue,
+        };
+        tasks.push(TaskUsage {
+            name: task_name(name.bytes()),
+            run_time: (run_time_ns / 1000) as u32,
+            stack_free: u32::MAX,
+        });
+    }
+    tasks
+}
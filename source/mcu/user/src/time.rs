@@ -0,0 +1,201 @@
+//! Clock of fake MCU.
+//!
+//! By default it follows wall-clock time. In virtual time mode it is advanced by SkifIO emulator
+//! by one sample period per sample, so simulation runs as fast as plant and IOC allow
+//! and its results do not depend on host timing.
+//!
+//! Timeouts of fake MCU are measured by this clock:
+//! + Blocking waits on `ustd` primitives and ring buffers (e.g. `BUFFER_TIMEOUT`) go through [`wait`].
+//! + Channel read timeout (IOC keep-alive) also goes through [`wait`].
+//! + SkifIO emulator times out waiting for sync pulse after missing them for the whole timeout.
+//!
+//! Virtual time stands still while no samples are transferred, so a timeout can only expire
+//! while control loop is running. Waiter holds virtual time at its deadline, so the timeout
+//! expires exactly at it regardless of how fast the waiting thread is scheduled.
+//!
+//! Peers running in the same process (e.g. emulated IOC) use [`hold`] to act at exact moments
+//! of virtual time.
+//!
+//! Tasks paced by `ustd` sleeps (statistics printer) run on real MCU only.
+
+extern crate std;
+
+use alloc::vec::Vec;
+use common::config;
+use core::time::Duration;
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex, OnceLock,
+    },
+    thread,
+    time::Instant,
+};
+
+/// Wall-clock timeout of a single attempt of timed wait in virtual time.
+const WAIT_SLICE: Duration = Duration::from_millis(1);
+
+#[derive(Default)]
+struct Holds {
+    /// Moments that virtual time must not pass, in nanoseconds.
+    at: Vec<u64>,
+    /// Clock is stopped by a hold.
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Virtual {
+    /// Nanoseconds elapsed since start, changed only with `holds` locked.
+    ns: AtomicU64,
+    holds: Mutex<Holds>,
+    changed: Condvar,
+}
+
+enum Clock {
+    Wall(Instant),
+    Virtual(Virtual),
+}
+
+static CLOCK: OnceLock<Clock> = OnceLock::new();
+
+impl Clock {
+    fn from_env() -> Self {
+        if env::var_os(config::VIRTUAL_TIME_ENV).is_some() {
+            Clock::Virtual(Virtual::default())
+        } else {
+            Clock::Wall(Instant::now())
+        }
+    }
+}
+
+fn clock() -> &'static Clock {
+    CLOCK.get_or_init(Clock::from_env)
+}
+
+/// Switch to virtual time regardless of environment.
+///
+/// Must be called before MCU is started.
+pub fn set_virtual() {
+    if CLOCK.set(Clock::Virtual(Virtual::default())).is_err() {
+        assert!(is_virtual(), "Clock is already running in wall-clock mode");
+    }
+}
+
+/// Whether clock runs in virtual time mode.
+pub fn is_virtual() -> bool {
+    matches!(clock(), Clock::Virtual(_))
+}
+
+/// Time elapsed since start.
+pub fn now() -> Duration {
+    match clock() {
+        Clock::Wall(start) => start.elapsed(),
+        Clock::Virtual(clock) => Duration::from_nanos(clock.ns.load(Ordering::Acquire)),
+    }
+}
+
+/// Advance virtual time by `dt`, does nothing in wall-clock mode.
+///
+/// Blocks while there is a [`Hold`] that virtual time would pass.
+pub fn advance(dt: Duration) {
+    if let Clock::Virtual(clock) = clock() {
+        let mut holds = clock.holds.lock().unwrap();
+        let ns = clock.ns.load(Ordering::Acquire) + dt.as_nanos() as u64;
+        while holds.at.iter().any(|&at| at < ns) {
+            holds.stopped = true;
+            clock.changed.notify_all();
+            holds = clock.changed.wait(holds).unwrap();
+        }
+        holds.stopped = false;
+        clock.ns.store(ns, Ordering::Release);
+        clock.changed.notify_all();
+    }
+}
+
+/// Virtual time does not pass held moment until guard is dropped.
+///
+/// Does nothing in wall-clock mode.
+pub struct Hold {
+    /// Held moment in nanoseconds.
+    at: u64,
+}
+
+/// Hold virtual time at `at`, or at the current time if `at` has already passed.
+pub fn hold(at: Duration) -> Hold {
+    let mut at = at.as_nanos() as u64;
+    if let Clock::Virtual(clock) = clock() {
+        let mut holds = clock.holds.lock().unwrap();
+        at = at.max(clock.ns.load(Ordering::Acquire));
+        holds.at.push(at);
+    }
+    Hold { at }
+}
+
+impl Hold {
+    /// Held moment.
+    pub fn at(&self) -> Duration {
+        Duration::from_nanos(self.at)
+    }
+
+    /// Block until held moment is reached.
+    ///
+    /// In virtual time it also waits until clock is stopped by the hold,
+    /// so the sample at held moment is already transferred and the next one is not.
+    pub fn wait(&self) {
+        match clock() {
+            Clock::Wall(_) => {
+                if let Some(delay) = self.at().checked_sub(now()) {
+                    thread::sleep(delay);
+                }
+            }
+            Clock::Virtual(clock) => {
+                let mut holds = clock.holds.lock().unwrap();
+                while !(holds.stopped && clock.ns.load(Ordering::Acquire) == self.at) {
+                    holds = clock.changed.wait(holds).unwrap();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        if let Clock::Virtual(clock) = clock() {
+            let mut holds = clock.holds.lock().unwrap();
+            let index = holds.at.iter().position(|&at| at == self.at).unwrap();
+            holds.at.swap_remove(index);
+            clock.changed.notify_all();
+        }
+    }
+}
+
+/// Perform blocking wait with `timeout` measured by clock.
+///
+/// `attempt` waits with given wall-clock timeout and returns `true` on success.
+/// In virtual time it is retried with short timeouts until it succeeds or `timeout` passes.
+pub fn wait(timeout: Option<Duration>, mut attempt: impl FnMut(Option<Duration>) -> bool) -> bool {
+    if !is_virtual() {
+        return attempt(timeout);
+    }
+    let hold = timeout.map(|timeout| hold(now() + timeout));
+    loop {
+        if attempt(Some(WAIT_SLICE)) {
+            return true;
+        }
+        if let Some(hold) = &hold {
+            if now() >= hold.at() {
+                return false;
+            }
+        }
+    }
+}
+
+/// Wall-clock timeout to pass to primitive that is retried by [`wait`].
+pub fn wait_slice(timeout: Option<Duration>) -> Option<Duration> {
+    if is_virtual() {
+        Some(WAIT_SLICE)
+    } else {
+        timeout
+    }
+}
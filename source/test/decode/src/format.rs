@@ -0,0 +1,136 @@
+use common::{
+    protocol::{AppMsg, AppMsgRef, McuMsg, McuMsgRef},
+    values::{uv_to_volt, Di, Do, Point, PointOpt},
+};
+use flatty::prelude::*;
+use std::fmt::Write;
+
+/// Number of points shown for waveform messages.
+const PREVIEW_POINTS: usize = 4;
+
+/// Message fields in human-readable form.
+pub fn app_msg_fields(msg: &AppMsg) -> String {
+    match msg.as_ref() {
+        AppMsgRef::KeepAlive { seq } => format!("seq={}", seq),
+        AppMsgRef::DoUpdate { value } => format!("value={}", do_(*value)),
+        AppMsgRef::AoState { enable } => format!("enable={}", enable.to_native()),
+        AppMsgRef::AoData { points } => waveform(points.as_slice()),
+        AppMsgRef::AoAdd { value } => format!("value={:.6}", uv_to_volt(*value)),
+        AppMsgRef::StatsReset { mask } => format!("mask=0x{:08x}", mask),
+        AppMsgRef::Goodbye | AppMsgRef::Hello => String::new(),
+        AppMsgRef::AoReadbackState { enable } => format!("enable={}", enable.to_native()),
+    }
+}
+
+/// Message fields in human-readable form.
+pub fn mcu_msg_fields(msg: &McuMsg) -> String {
+    match msg.as_ref() {
+        McuMsgRef::DiUpdate { value } => format!("value={}", di(*value)),
+        McuMsgRef::AoRequest { count } => format!("count={}", count),
+        McuMsgRef::AiData { points } => {
+            let points = points.as_slice();
+            let mut s = format!("points={}", points.len());
+            if let (Some(first), Some(last)) = (points.first(), points.last()) {
+                write!(s, " first={} last={}", channels(first), channels(last)).unwrap();
+            }
+            s
+        }
+        McuMsgRef::Error { code, message } => format!(
+            "code={} message={:?}",
+            code,
+            String::from_utf8_lossy(message.as_slice())
+        ),
+        McuMsgRef::Debug { message } => {
+            format!("message={:?}", String::from_utf8_lossy(message.as_slice()))
+        }
+        McuMsgRef::AoReadback { points } => waveform(points.as_slice()),
+        McuMsgRef::SkifioState { temp, status } => {
+            format!("temp={} status={:#04x}", temp, status)
+        }
+        McuMsgRef::KeepAliveAck { seq } => format!("seq={}", seq),
+        McuMsgRef::StateSync {
+            di: di_value,
+            do_: do_value,
+            ao_enabled,
+            ao_add,
+        } => format!(
+            "di={} do={} ao_enabled={} ao_add={:.6}",
+            di(*di_value),
+            do_(*do_value),
+            ao_enabled.to_native(),
+            uv_to_volt(*ao_add)
+        ),
+        McuMsgRef::LoopStats {
+            cycle,
+            wait_ready,
+            cycle_max,
+            wait_ready_max,
+        } => format!(
+            "cycle={:?} wait_ready={:?} cycle_max={} wait_ready_max={}",
+            cycle, wait_ready, cycle_max, wait_ready_max
+        ),
+        McuMsgRef::TaskStats {
+            run_time_freq,
+            tasks,
+        } => {
+            let mut s = format!("run_time_freq={}", run_time_freq);
+            for task in tasks.iter() {
+                let name = String::from_utf8_lossy(&task.name);
+                write!(
+                    s,
+                    " {}=({}, {})",
+                    name.trim_end_matches('\0'),
+                    task.run_time,
+                    task.stack_free
+                )
+                .unwrap();
+            }
+            s
+        }
+        McuMsgRef::StatsCounters {
+            sync,
+            samples,
+            crc_errors,
+            ao_lost,
+            time_us,
+        } => format!(
+            "sync={} samples={} crc_errors={} ao_lost={} time_us={}",
+            sync, samples, crc_errors, ao_lost, time_us
+        ),
+    }
+}
+
+fn di(value: Di) -> String {
+    format!("{:0width$b}", u8::from(value), width = Di::SIZE)
+}
+
+fn do_(value: Do) -> String {
+    format!("{:0width$b}", u8::from(value), width = Do::SIZE)
+}
+
+fn point(value: Point) -> String {
+    match value.into_opt() {
+        PointOpt::Uv(uv) => format!("{:.6}", uv_to_volt(uv)),
+        PointOpt::Sep => "sep".into(),
+    }
+}
+
+/// Values of all AI channels in a single point.
+fn channels(values: &[Point]) -> String {
+    let values: Vec<_> = values.iter().copied().map(point).collect();
+    format!("[{}]", values.join(", "))
+}
+
+/// Number of points and the first of them.
+fn waveform(points: &[Point]) -> String {
+    let mut preview: Vec<_> = points
+        .iter()
+        .take(PREVIEW_POINTS)
+        .copied()
+        .map(point)
+        .collect();
+    if points.len() > PREVIEW_POINTS {
+        preview.push("...".into());
+    }
+    format!("points={} [{}]", points.len(), preview.join(", "))
+}
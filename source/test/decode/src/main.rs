@@ -0,0 +1,248 @@
+//! Decode messages passed between IOC and MCU into human-readable lines.
+
+mod format;
+
+use common::{
+    capture::{Direction, RecordHeader, MAGIC},
+    config::{MAX_APP_MSG_LEN, MAX_MCU_MSG_LEN},
+    protocol::{AppMsg, McuMsg},
+};
+use flatty::{prelude::*, traits::FlatBase};
+use std::{
+    env,
+    fs::File,
+    io::{self, Read},
+    process::exit,
+    time::Duration,
+};
+
+const USAGE: &str = "\
+Usage: decode [OPTIONS] [FILE]
+
+Decode messages passed between IOC and MCU. Reads standard input if FILE is not given.
+Capture files are detected automatically.
+
+Options:
+    --raw            Input is a raw stream of messages, e.g. dump of /dev/rpmsg0
+    --framed         Input is a stream of length-prefixed messages, e.g. dump of fake channel
+    --from <SIDE>    Sender of raw or framed messages: `mcu` (default) or `app`
+    --type <NAMES>   Show only messages of comma-separated types, may be repeated
+    -h, --help       Print this help";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Capture,
+    Raw,
+    Framed,
+}
+
+struct Args {
+    format: Format,
+    from: Direction,
+    types: Vec<String>,
+    path: Option<String>,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    exit(2);
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut args = Args {
+            format: Format::Capture,
+            from: Direction::McuToApp,
+            types: Vec::new(),
+            path: None,
+        };
+        let mut iter = env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--raw" => args.format = Format::Raw,
+                "--framed" => args.format = Format::Framed,
+                "--from" => {
+                    args.from = match iter.next().as_deref() {
+                        Some("mcu") => Direction::McuToApp,
+                        Some("app") => Direction::AppToMcu,
+                        _ => usage_error("`--from` must be `mcu` or `app`"),
+                    }
+                }
+                "--type" => match iter.next() {
+                    Some(names) => args.types.extend(names.split(',').map(String::from)),
+                    None => usage_error("`--type` requires message types"),
+                },
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    exit(0);
+                }
+                _ if arg.starts_with('-') => usage_error(&format!("Unknown option `{}`", arg)),
+                _ if args.path.is_none() => args.path = Some(arg),
+                _ => usage_error("Only one input file can be given"),
+            }
+        }
+        args
+    }
+}
+
+/// Decodes messages from bytes read so far.
+struct Decoder {
+    args: Args,
+    /// Capture signature has been checked.
+    magic_checked: bool,
+    /// Offset of the first undecoded byte in input.
+    offset: usize,
+}
+
+impl Decoder {
+    fn new(args: Args) -> Self {
+        Self {
+            args,
+            magic_checked: false,
+            offset: 0,
+        }
+    }
+
+    /// Decode complete messages in `data` and return number of consumed bytes.
+    fn decode(&mut self, data: &[u8], eof: bool) -> Result<usize, String> {
+        let mut pos = 0;
+        loop {
+            let rest = &data[pos..];
+            let len = match self.args.format {
+                Format::Capture => self.decode_record(rest)?,
+                Format::Framed => self.decode_frame(rest)?,
+                Format::Raw => self.decode_raw(rest, eof)?,
+            };
+            match len {
+                Some(len) => {
+                    pos += len;
+                    self.offset += len;
+                }
+                None => break Ok(pos),
+            }
+        }
+    }
+
+    fn decode_record(&mut self, data: &[u8]) -> Result<Option<usize>, String> {
+        if !self.magic_checked {
+            if data.len() < MAGIC.len() {
+                return Ok(None);
+            }
+            if data[..MAGIC.len()] != MAGIC {
+                return Err("Not a capture, use `--raw` or `--framed` for other input".into());
+            }
+            self.magic_checked = true;
+            return Ok(Some(MAGIC.len()));
+        }
+        let header = match data.get(..RecordHeader::SIZE) {
+            Some(bytes) => RecordHeader::from_bytes(bytes.try_into().unwrap())
+                .ok_or_else(|| format!("Malformed record at {:#x}", self.offset))?,
+            None => return Ok(None),
+        };
+        let end = RecordHeader::SIZE + header.len as usize;
+        let msg = match data.get(RecordHeader::SIZE..end) {
+            Some(msg) => msg,
+            None => return Ok(None),
+        };
+        let time = Duration::from_nanos(header.time_ns);
+        let prefix = format!("{:>12.6} ch{}", time.as_secs_f64(), header.channel);
+        self.print(&prefix, header.direction, msg)?;
+        Ok(Some(end))
+    }
+
+    fn decode_frame(&mut self, data: &[u8]) -> Result<Option<usize>, String> {
+        let len = match data.get(..2) {
+            Some(bytes) => u16::from_le_bytes(bytes.try_into().unwrap()) as usize,
+            None => return Ok(None),
+        };
+        let msg = match data.get(2..(2 + len)) {
+            Some(msg) => msg,
+            None => return Ok(None),
+        };
+        self.print(&format!("{:08x}", self.offset), self.args.from, msg)?;
+        Ok(Some(2 + len))
+    }
+
+    fn decode_raw(&mut self, data: &[u8], eof: bool) -> Result<Option<usize>, String> {
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let (max_len, res) = match self.args.from {
+            Direction::AppToMcu => (MAX_APP_MSG_LEN, AppMsg::from_bytes(data).map(|m| m.size())),
+            Direction::McuToApp => (MAX_MCU_MSG_LEN, McuMsg::from_bytes(data).map(|m| m.size())),
+        };
+        match res {
+            Ok(len) => {
+                self.print(
+                    &format!("{:08x}", self.offset),
+                    self.args.from,
+                    &data[..len],
+                )?;
+                Ok(Some(len))
+            }
+            // Message may be incomplete, wait for more data.
+            Err(_) if !eof && data.len() < max_len => Ok(None),
+            Err(err) => Err(format!(
+                "Cannot parse message at {:#x}: {:?}",
+                self.offset, err
+            )),
+        }
+    }
+
+    fn print(&self, prefix: &str, direction: Direction, msg: &[u8]) -> Result<(), String> {
+        let parse_error = |err| format!("Cannot parse message at {:#x}: {:?}", self.offset, err);
+        let (arrow, name, fields) = match direction {
+            Direction::AppToMcu => {
+                let msg = AppMsg::from_bytes(msg).map_err(parse_error)?;
+                ("app->mcu", msg.as_ref().name(), format::app_msg_fields(msg))
+            }
+            Direction::McuToApp => {
+                let msg = McuMsg::from_bytes(msg).map_err(parse_error)?;
+                ("mcu->app", msg.as_ref().name(), format::mcu_msg_fields(msg))
+            }
+        };
+        let types = &self.args.types;
+        if types.is_empty() || types.iter().any(|t| t.eq_ignore_ascii_case(name)) {
+            println!("{} {} {} {}", prefix, arrow, name, fields);
+        }
+        Ok(())
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let mut input: Box<dyn Read> = match &args.path {
+        Some(path) => {
+            Box::new(File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?)
+        }
+        None => Box::new(io::stdin()),
+    };
+    let mut decoder = Decoder::new(args);
+    let mut data = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        let n = input
+            .read(&mut chunk)
+            .map_err(|e| format!("Cannot read input: {}", e))?;
+        let eof = n == 0;
+        data.extend_from_slice(&chunk[..n]);
+        let consumed = decoder.decode(&data, eof)?;
+        data.drain(..consumed);
+        if eof {
+            break;
+        }
+    }
+    if !data.is_empty() {
+        return Err(format!(
+            "Input ends with incomplete message of {} bytes",
+            data.len()
+        ));
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run(Args::parse()) {
+        eprintln!("Error: {}", err);
+        exit(1);
+    }
+}
@@ -1,49 +1,45 @@
-use common::{
-    config::{AI_COUNT, DO_BITS, SAMPLE_PERIOD},
-    values::{try_volt_to_uv, Uv},
+use fakedev::{
+    generator::spawn_plant,
+    plant::{Dummy, RlLoad, RlParams},
+    run,
 };
-use fakedev::run;
-use std::{f64::consts::PI, time::Duration};
-use tokio::{main as async_main, task::spawn, time::sleep};
+use std::{env, time::Duration};
+use tokio::{main as async_main, time::sleep};
 
-const FREQS: [f64; AI_COUNT] = [0.0, 1.0, PI, 10.0, 10.0 * PI, 100.0];
+/// Environment variable selecting plant model: `dummy` (default) or `rl`.
+const PLANT_ENV: &str = "TORNADO_FAKEDEV_PLANT";
+/// Prefix of environment variables overriding `RlParams` fields.
+/// Field name in upper case is appended to it, e.g. `TORNADO_FAKEDEV_RL_INDUCTANCE`.
+const RL_PARAMS_ENV_PREFIX: &str = "TORNADO_FAKEDEV_RL_";
 
-extern "C" {
-    fn user_sample_intr();
+/// Read `RlParams` from environment variables, missing ones are taken from defaults.
+fn rl_params() -> RlParams {
+    let mut params = RlParams::default();
+    for (name, value) in [
+        ("INDUCTANCE", &mut params.inductance),
+        ("RESISTANCE", &mut params.resistance),
+        ("SUPPLY_GAIN", &mut params.supply_gain),
+        ("CURRENT_GAIN", &mut params.current_gain),
+        ("VOLTAGE_GAIN", &mut params.voltage_gain),
+        ("NOISE", &mut params.noise),
+    ] {
+        let var = format!("{}{}", RL_PARAMS_ENV_PREFIX, name);
+        if let Ok(text) = env::var(&var) {
+            *value = text
+                .parse()
+                .unwrap_or_else(|_| panic!("Bad {}: {}", var, text));
+        }
+    }
+    params
 }
 
 #[async_main]
 async fn main() {
-    let mut skifio = run();
-    let mut phases = [0.0_f64; AI_COUNT];
-    spawn(async move {
-        let mut counter: u64 = 0;
-        let mut ais = [Uv::default(); AI_COUNT];
-        loop {
-            skifio.ais.send(ais).await.unwrap();
-            unsafe { user_sample_intr() };
-
-            let ao = skifio.ao.recv().await.unwrap();
-            ais[0] = ao;
-            for i in 1..AI_COUNT {
-                ais[i] = try_volt_to_uv(phases[i].sin()).unwrap();
-                phases[i] = 2.0 * PI * FREQS[i] * counter as f64 * SAMPLE_PERIOD.as_secs_f64();
-            }
-
-            const BATCH: usize = 1000;
-            counter += 1;
-            if counter % BATCH as u64 == 0 {
-                sleep(SAMPLE_PERIOD * BATCH as u32).await;
-            }
-        }
-    });
-    spawn(async move {
-        loop {
-            let mut value = u8::from(skifio.do_.recv().await.unwrap());
-            value |= value << DO_BITS;
-            skifio.di.send(value.try_into().unwrap()).await.unwrap();
-        }
-    });
+    match env::var(PLANT_ENV).as_deref() {
+        Err(_) | Ok("dummy") => spawn_plant(run(), Dummy::default()),
+        Ok("rl") => spawn_plant(run(), RlLoad::new(rl_params())),
+        Ok(other) => panic!("Unknown plant model: {}", other),
+    }
     loop {
         sleep(Duration::from_millis(100)).await;
     }
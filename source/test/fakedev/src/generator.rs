@@ -0,0 +1,57 @@
+use crate::{
+    plant::{Dummy, Plant},
+    Skifio,
+};
+use common::{
+    config::{AI_COUNT, DO_BITS, SAMPLE_PERIOD},
+    values::Uv,
+};
+use mcu::time;
+use tokio::{task::spawn, time::sleep};
+
+extern "C" {
+    fn user_sample_intr();
+}
+
+/// Emulate SkifIO board connected to `plant` and echo DO to DI.
+///
+/// In virtual time plant is attached to emulator, otherwise it is paced to real time.
+///
+/// Must be called within Tokio runtime.
+pub fn spawn_plant<P: Plant>(mut skifio: Skifio, mut plant: P) {
+    if time::is_virtual() {
+        skifio.attach_plant(plant);
+    } else {
+        spawn(async move {
+            let mut counter: u64 = 0;
+            let mut ais = [Uv::default(); AI_COUNT];
+            loop {
+                skifio.ais.send(ais).await.unwrap();
+                unsafe { user_sample_intr() };
+
+                let ao = skifio.ao.recv().await.unwrap();
+                ais = plant.step(ao);
+
+                const BATCH: usize = 1000;
+                counter += 1;
+                if counter % BATCH as u64 == 0 {
+                    sleep(SAMPLE_PERIOD * BATCH as u32).await;
+                }
+            }
+        });
+    }
+    spawn(async move {
+        loop {
+            let mut value = u8::from(skifio.do_.recv().await.unwrap());
+            value |= value << DO_BITS;
+            skifio.di.send(value.try_into().unwrap()).await.unwrap();
+        }
+    });
+}
+
+/// Emulate SkifIO board: loop AO back to the first AI, generate sine waves on others and echo DO to DI.
+///
+/// Must be called within Tokio runtime.
+pub fn spawn_dummy(skifio: Skifio) {
+    spawn_plant(skifio, Dummy::default());
+}
@@ -1,8 +1,10 @@
 pub mod epics;
+pub mod generator;
+pub mod plant;
 pub mod skifio;
 
 pub use epics::Epics;
-pub use skifio::SkifioHandle as Skifio;
+pub use skifio::{FaultCounters, FaultPlan, Faults, SkifioHandle as Skifio};
 
 use mcu::user_main;
 
@@ -0,0 +1,125 @@
+use common::{
+    config::{AI_COUNT, SAMPLE_PERIOD},
+    values::{try_volt_to_uv, uv_to_volt, volt_to_uv_saturating, Uv},
+};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro128PlusPlus as SomeRng;
+use std::f64::consts::PI;
+
+/// Input range of SkifIO ADC in volts, sensor outputs are clamped to it.
+const AI_RANGE: f64 = 10.0;
+
+/// Load driven by AO samples that produces AI samples.
+pub trait Plant: Send + 'static {
+    /// Apply `ao` during one sample period and return AI values at the end of it.
+    fn step(&mut self, ao: Uv) -> [Uv; AI_COUNT];
+}
+
+const FREQS: [f64; AI_COUNT] = [0.0, 1.0, PI, 10.0, 10.0 * PI, 100.0];
+
+/// Loops AO back to the first AI and generates sine waves on others.
+#[derive(Default)]
+pub struct Dummy {
+    counter: u64,
+    phases: [f64; AI_COUNT],
+}
+
+impl Plant for Dummy {
+    fn step(&mut self, ao: Uv) -> [Uv; AI_COUNT] {
+        let mut ais = [ao; AI_COUNT];
+        for i in 1..AI_COUNT {
+            ais[i] = try_volt_to_uv(self.phases[i].sin()).unwrap();
+            self.phases[i] =
+                2.0 * PI * FREQS[i] * self.counter as f64 * SAMPLE_PERIOD.as_secs_f64();
+        }
+        self.counter += 1;
+        ais
+    }
+}
+
+/// Parameters of magnet load.
+#[derive(Clone, Debug)]
+pub struct RlParams {
+    /// Inductance in henries.
+    pub inductance: f64,
+    /// Resistance in ohms.
+    pub resistance: f64,
+    /// Power supply output voltage per AO volt.
+    pub supply_gain: f64,
+    /// Current sensor output in volts per ampere.
+    pub current_gain: f64,
+    /// Voltage sensor output in volts per load volt.
+    pub voltage_gain: f64,
+    /// Amplitude of uniform noise added to sensor outputs in volts.
+    pub noise: f64,
+}
+
+impl Default for RlParams {
+    fn default() -> Self {
+        Self {
+            inductance: 0.1,
+            resistance: 1.0,
+            supply_gain: 1.0,
+            current_gain: 1.0,
+            voltage_gain: 1.0,
+            noise: 1e-3,
+        }
+    }
+}
+
+/// Magnet with inductance and resistance fed by power supply controlled by AO.
+///
+/// AI channels are: AO loopback, current sensor, voltage sensor and noise on the rest.
+pub struct RlLoad {
+    params: RlParams,
+    /// Current through load in amperes.
+    current: f64,
+    rng: SomeRng,
+}
+
+impl RlLoad {
+    pub fn new(params: RlParams) -> Self {
+        Self {
+            params,
+            current: 0.0,
+            rng: SomeRng::seed_from_u64(0xdeadbeef),
+        }
+    }
+
+    fn sensor(&mut self, value: f64) -> Uv {
+        let noise = self.params.noise;
+        let value = value + self.rng.gen_range(-noise..=noise);
+        volt_to_uv_saturating(value.clamp(-AI_RANGE, AI_RANGE))
+    }
+}
+
+impl Plant for RlLoad {
+    fn step(&mut self, ao: Uv) -> [Uv; AI_COUNT] {
+        let RlParams {
+            inductance,
+            resistance,
+            supply_gain,
+            current_gain,
+            voltage_gain,
+            ..
+        } = self.params;
+        let dt = SAMPLE_PERIOD.as_secs_f64();
+        let voltage = supply_gain * uv_to_volt(ao);
+        // Exact solution of `L dI/dt + R I = V` for constant voltage during sample period.
+        self.current = if resistance > 0.0 {
+            let decay = (-resistance * dt / inductance).exp();
+            self.current * decay + voltage / resistance * (1.0 - decay)
+        } else {
+            self.current + voltage * dt / inductance
+        };
+
+        let mut ais = [0; AI_COUNT];
+        ais[0] = ao;
+        ais[1] = self.sensor(current_gain * self.current);
+        ais[2] = self.sensor(voltage_gain * voltage);
+        for ai in ais.iter_mut().skip(3) {
+            *ai = self.sensor(0.0);
+        }
+        ais
+    }
+}
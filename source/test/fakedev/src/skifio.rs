@@ -1,14 +1,21 @@
+use crate::plant::Plant;
 use common::{
-    config::AI_COUNT,
+    config::{AI_COUNT, SAMPLE_PERIOD},
     values::{AtomicBits, Di, Do, Uv},
 };
 use futures::{future::pending, FutureExt};
 use mcu::{
     error::{Error, ErrorKind, ErrorSource},
     skifio::{self, DiHandler, SkifioIface, SKIFIO},
+    time,
 };
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro128PlusPlus as SomeRng;
 use std::{
-    sync::{atomic::Ordering, Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread::{park, sleep},
     time::Duration,
 };
@@ -21,16 +28,110 @@ use tokio::{
 };
 use ustd::task::InterruptContext;
 
+extern "C" {
+    fn user_sample_intr();
+}
+
 const AI_CHAN_CAP: usize = 256;
 const AO_CHAN_CAP: usize = 256;
 const DI_CHAN_CAP: usize = 16;
 const DO_CHAN_CAP: usize = 16;
+const PLANT_CHAN_CAP: usize = 1;
+
+/// Faults injected by emulator, no faults by default.
+#[derive(Clone, Debug)]
+pub struct FaultPlan {
+    /// Probability of CRC error in a single transfer, must be in `0.0..=1.0`.
+    pub crc_error_rate: f64,
+    /// Probability of missing sync pulse, must be in `0.0..=1.0`.
+    ///
+    /// Sample is lost and waiting for it times out.
+    /// In virtual time the missed sample takes its sample period and waiting goes on until timeout.
+    pub missed_sync_rate: f64,
+    /// DI value read from board regardless of what is sent to emulator.
+    pub stuck_di: Option<Di>,
+    /// Initial board temperature.
+    pub temp: i8,
+    /// Temperature change in degrees per second of emulated time.
+    pub temp_ramp: f64,
+    /// Status bits reported by board.
+    pub status: u8,
+}
+
+impl Default for FaultPlan {
+    fn default() -> Self {
+        Self {
+            crc_error_rate: 0.0,
+            missed_sync_rate: 0.0,
+            stuck_di: None,
+            temp: 36,
+            temp_ramp: 0.0,
+            status: 0,
+        }
+    }
+}
+
+impl FaultPlan {
+    /// Panics if plan cannot be applied.
+    fn check(&self) {
+        for (name, rate) in [
+            ("CRC error", self.crc_error_rate),
+            ("missed sync", self.missed_sync_rate),
+        ] {
+            assert!(
+                (0.0..=1.0).contains(&rate),
+                "{} rate must be in 0.0..=1.0, got {}",
+                name,
+                rate
+            );
+        }
+    }
+}
+
+/// Number of faults injected so far.
+#[derive(Debug, Default)]
+pub struct FaultCounters {
+    pub crc_errors: AtomicUsize,
+    pub missed_syncs: AtomicUsize,
+}
+
+/// Faults of running emulator, handle may be cloned and kept after emulator is spawned.
+#[derive(Clone)]
+pub struct Faults {
+    plan: Arc<Mutex<FaultPlan>>,
+    pub injected: Arc<FaultCounters>,
+}
+
+impl Faults {
+    pub fn plan(&self) -> FaultPlan {
+        self.plan.lock().unwrap().clone()
+    }
+    /// Replace fault plan, panics if it is invalid.
+    pub fn set_plan(&self, plan: FaultPlan) {
+        plan.check();
+        *self.plan.lock().unwrap() = plan;
+    }
+}
 
 pub struct SkifioHandle {
     pub ao: Receiver<Uv>,
     pub ais: Sender<[Uv; AI_COUNT]>,
     pub do_: Receiver<Do>,
     pub di: Sender<Di>,
+    /// Fault plan may be changed while emulator is running.
+    pub faults: Faults,
+    plant: Sender<Box<dyn Plant>>,
+}
+
+impl SkifioHandle {
+    /// Step `plant` inside emulator instead of exchanging samples over `ao` and `ais`.
+    ///
+    /// Avoids switching threads on each sample, so it is used to run simulation in virtual time.
+    pub fn attach_plant<P: Plant>(&self, plant: P) {
+        assert!(time::is_virtual(), "Plant is attached in virtual time only");
+        let attached = self.plant.try_send(Box::new(plant)).is_ok();
+        assert!(attached, "Plant is already attached");
+    }
 }
 
 struct Skifio {
@@ -39,6 +140,13 @@ struct Skifio {
     ais: Receiver<[Uv; AI_COUNT]>,
     last_ais: Option<[Uv; AI_COUNT]>,
 
+    /// AO value sent on the last transfer.
+    last_ao: Uv,
+
+    plant_recv: Receiver<Box<dyn Plant>>,
+    /// Attached plant and AIs of the next sample.
+    plant: Option<(Box<dyn Plant>, [Uv; AI_COUNT])>,
+
     do_: Sender<Do>,
     last_di: Arc<AtomicBits>,
     di_handler: Arc<Mutex<Option<Box<dyn DiHandler>>>>,
@@ -46,6 +154,10 @@ struct Skifio {
     runtime: Runtime,
 
     count: usize,
+
+    faults: Arc<Mutex<FaultPlan>>,
+    injected: Arc<FaultCounters>,
+    rng: SomeRng,
 }
 
 impl Skifio {
@@ -54,12 +166,15 @@ impl Skifio {
         let (ais_send, ais_recv) = channel(AI_CHAN_CAP);
         let (do_send, do_recv) = channel(DO_CHAN_CAP);
         let (di_send, di_recv) = channel(DI_CHAN_CAP);
+        let (plant_send, plant_recv) = channel(PLANT_CHAN_CAP);
         let last_di = Arc::new(AtomicBits::default());
         let di_handler = Arc::new(Mutex::new(None::<Box<dyn DiHandler>>));
+        let faults = Arc::new(Mutex::new(FaultPlan::default()));
         {
             let mut recv = di_recv;
             let handler = di_handler.clone();
             let last = last_di.clone();
+            let faults = faults.clone();
             spawn(async move {
                 loop {
                     let din: Di = match recv.recv().await {
@@ -67,6 +182,9 @@ impl Skifio {
                         None => pending().await, // Channel closed
                     };
                     last.store(din.into(), Ordering::Release);
+                    if faults.lock().unwrap().stuck_di.is_some() {
+                        continue;
+                    }
                     if let Some(cb) = &mut *handler.lock().unwrap() {
                         let mut ctx = InterruptContext::new();
                         cb(&mut ctx, din);
@@ -74,6 +192,7 @@ impl Skifio {
                 }
             });
         }
+        let injected = Arc::new(FaultCounters::default());
         let runtime = runtime::Builder::new_current_thread()
             .enable_time()
             .build()
@@ -84,22 +203,115 @@ impl Skifio {
                 ao_enabled: false,
                 ais: ais_recv,
                 last_ais: None,
+                last_ao: Uv::default(),
+                plant_recv,
+                plant: None,
                 do_: do_send,
                 last_di,
                 di_handler,
                 runtime,
                 count: 0,
+                faults: faults.clone(),
+                injected: injected.clone(),
+                rng: SomeRng::seed_from_u64(0xdeadbeef),
             },
             SkifioHandle {
                 ao: ao_recv,
                 ais: ais_send,
                 do_: do_recv,
                 di: di_send,
+                faults: Faults {
+                    plan: faults,
+                    injected,
+                },
+                plant: plant_send,
             },
         )
     }
 }
 
+fn timed_out() -> Error {
+    Error {
+        kind: ErrorKind::TimedOut,
+        source: ErrorSource::None,
+    }
+}
+
+impl Skifio {
+    /// Whether sync pulse of received sample is missed, then the sample is dropped.
+    fn miss_sync(&mut self) -> bool {
+        let missed_sync_rate = self.faults.lock().unwrap().missed_sync_rate;
+        if !self.rng.gen_bool(missed_sync_rate) {
+            return false;
+        }
+        self.last_ais = None;
+        self.injected.missed_syncs.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Apply `ao` to plant.
+    fn send_ao(&mut self, ao: Uv) {
+        match &mut self.plant {
+            Some((plant, next_ais)) => *next_ais = plant.step(ao),
+            None => self.ao.try_send(ao).unwrap(),
+        }
+    }
+
+    /// Take AIs of the next sample from plant, `None` if plant is gone.
+    fn recv_ais(&mut self) -> Option<[Uv; AI_COUNT]> {
+        if self.plant.is_none() {
+            let received = self.runtime.block_on(async {
+                select! {
+                    biased;
+                    Some(plant) = self.plant_recv.recv() => Err(plant),
+                    ais = self.ais.recv() => Ok(ais),
+                }
+            });
+            match received {
+                Ok(ais) => {
+                    let ais = ais?;
+                    self.runtime.block_on(self.ao.reserve()).ok()?;
+                    return Some(ais);
+                }
+                // Plant starts from zero AIs as if it was connected through channels.
+                Err(plant) => self.plant = Some((plant, [Uv::default(); AI_COUNT])),
+            }
+        }
+        let (_, ais) = self.plant.as_ref().unwrap();
+        let ais = *ais;
+        unsafe { user_sample_intr() };
+        Some(ais)
+    }
+
+    /// Sync pulses come every sample period of virtual time, missed ones take their period too.
+    fn wait_ready_virtual(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        let deadline = timeout.map(|timeout| time::now() + timeout);
+        loop {
+            match self.recv_ais() {
+                Some(ais) => self.last_ais = Some(ais),
+                // Plant is gone, so no more sync pulses will come.
+                None => match deadline {
+                    Some(deadline) => {
+                        time::advance(deadline.saturating_sub(time::now()));
+                        return Err(timed_out());
+                    }
+                    None => loop {
+                        park();
+                    },
+                },
+            }
+            if !self.miss_sync() {
+                return Ok(());
+            }
+            time::advance(SAMPLE_PERIOD);
+            self.send_ao(self.last_ao);
+            if deadline.map_or(false, |deadline| time::now() >= deadline) {
+                return Err(timed_out());
+            }
+        }
+    }
+}
+
 impl SkifioIface for Skifio {
     fn set_ao_state(&mut self, enabled: bool) -> Result<(), Error> {
         self.ao_enabled = enabled;
@@ -113,6 +325,9 @@ impl SkifioIface for Skifio {
         if self.last_ais.is_some() {
             return Ok(());
         }
+        if time::is_virtual() {
+            return self.wait_ready_virtual(timeout);
+        }
         let fut = async {
             if self.last_ais.is_none() {
                 let adcs = match self.ais.recv().await {
@@ -133,7 +348,7 @@ impl SkifioIface for Skifio {
                 None => Some(fut.await),
             }
         };
-        let ready = match self.runtime.block_on(fut_timed) {
+        let mut ready = match self.runtime.block_on(fut_timed) {
             Some(alive) => {
                 if alive {
                     true
@@ -152,13 +367,15 @@ impl SkifioIface for Skifio {
             }
             None => false,
         };
+        if ready && self.miss_sync() {
+            // Still return AO to keep emulator going.
+            self.send_ao(self.last_ao);
+            ready = false;
+        }
         if ready {
             Ok(())
         } else {
-            Err(Error {
-                kind: ErrorKind::TimedOut,
-                source: ErrorSource::None,
-            })
+            Err(timed_out())
         }
     }
     fn transfer(&mut self, out: skifio::XferOut) -> Result<skifio::XferIn, Error> {
@@ -170,11 +387,25 @@ impl SkifioIface for Skifio {
         };
         let ais = self.last_ais.take().unwrap();
         self.count += 1;
-        self.ao.try_send(ao).unwrap();
+        time::advance(SAMPLE_PERIOD);
+        self.last_ao = ao;
+        self.send_ao(ao);
+
+        let faults = self.faults.lock().unwrap().clone();
+        if self.rng.gen_bool(faults.crc_error_rate) {
+            self.injected.crc_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(Error {
+                kind: ErrorKind::InvalidData,
+                source: ErrorSource::Other("CRC error"),
+            });
+        }
+        let elapsed = (SAMPLE_PERIOD * self.count as u32).as_secs_f64();
+        let temp = faults.temp as f64 + faults.temp_ramp * elapsed;
         Ok(skifio::XferIn {
             ais,
-            temp: 36,
-            status: 0,
+            // Float to int cast saturates.
+            temp: temp as i8,
+            status: faults.status,
         })
     }
 
@@ -184,7 +415,10 @@ impl SkifioIface for Skifio {
     }
 
     fn read_di(&mut self) -> Di {
-        self.last_di.load(Ordering::Acquire).try_into().unwrap()
+        match self.faults.lock().unwrap().stuck_di {
+            Some(value) => value,
+            None => self.last_di.load(Ordering::Acquire).try_into().unwrap(),
+        }
     }
     fn subscribe_di(&mut self, callback: Option<Box<dyn DiHandler>>) -> Result<(), Error> {
         *self.di_handler.lock().unwrap() = callback;
@@ -0,0 +1,246 @@
+use super::{
+    channel::{FramedRead, FramedWrite, MsgReader, MsgWriter},
+    reach,
+};
+use common::{
+    config::{self, KEEP_ALIVE_PERIOD, MAX_APP_MSG_LEN, MAX_MCU_MSG_LEN},
+    protocol::{self as proto, AppMsg, AppMsgMut, McuMsg, McuMsgRef},
+    values::{Point, Uv},
+};
+use flatty::{flat_vec, prelude::*, Emplacer};
+use mcu::time;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{split, ReadHalf, WriteHalf},
+    sync::{mpsc::unbounded_channel, watch},
+    task::{spawn, JoinHandle},
+    time::sleep,
+};
+
+#[cfg(not(any(feature = "unix", feature = "loopback")))]
+type Stream = tokio::net::TcpStream;
+#[cfg(any(feature = "unix", feature = "loopback"))]
+type Stream = tokio::net::UnixStream;
+
+#[allow(clippy::let_and_return)]
+async fn try_open(id: u32) -> io::Result<Stream> {
+    #[cfg(not(any(feature = "unix", feature = "loopback")))]
+    let stream = Stream::connect((config::CHANNEL_HOST, config::channel_port(id))).await;
+    #[cfg(all(feature = "unix", not(feature = "loopback")))]
+    let stream = Stream::connect(config::channel_socket(id)).await;
+    #[cfg(feature = "loopback")]
+    let stream = mcu::channel::connect(id).and_then(|stream| {
+        stream.set_nonblocking(true)?;
+        Stream::from_std(stream)
+    });
+    stream
+}
+
+/// Connect to channel `id`, waiting for MCU to start listening.
+async fn open(id: u32) -> Stream {
+    const ATTEMPTS: usize = 50;
+    let mut attempt = 0;
+    loop {
+        match try_open(id).await {
+            Ok(stream) => break stream,
+            Err(err) => {
+                attempt += 1;
+                assert!(
+                    attempt < ATTEMPTS,
+                    "Cannot connect to channel {}: {}",
+                    id,
+                    err
+                );
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+type Reader = MsgReader<McuMsg, FramedRead<ReadHalf<Stream>>>;
+type Writer = MsgWriter<AppMsg, FramedWrite<WriteHalf<Stream>>>;
+
+fn reader(stream: ReadHalf<Stream>) -> Reader {
+    MsgReader::new(FramedRead::new(stream), MAX_MCU_MSG_LEN)
+}
+fn writer(stream: WriteHalf<Stream>) -> Writer {
+    MsgWriter::new(FramedWrite::new(stream), MAX_APP_MSG_LEN)
+}
+
+async fn send<E: Emplacer<AppMsg>>(writer: &mut Writer, emplacer: E) -> io::Result<()> {
+    writer
+        .alloc_message()
+        .new_in_place(emplacer)
+        .unwrap()
+        .write()
+        .await
+}
+
+/// Number of messages received from MCU.
+#[derive(Default)]
+pub struct Counters {
+    pub state_syncs: AtomicUsize,
+    pub ao_requests: AtomicUsize,
+    pub ai_data: AtomicUsize,
+    /// Sample count from the last `StatsCounters` message.
+    pub mcu_samples: AtomicUsize,
+    /// CRC error count from the last `StatsCounters` message.
+    pub mcu_crc_errors: AtomicUsize,
+}
+
+/// IOC side of MCU channels.
+///
+/// Sends keep-alive messages, answers AO requests with constant value and counts received AI data.
+/// Connections are closed on drop.
+///
+/// Keep-alive messages are sent at exact moments of MCU time and each one waits for acknowledgement,
+/// so in virtual time MCU sees the same timing on every run.
+pub struct App {
+    pub counters: Arc<Counters>,
+    paused: Arc<AtomicBool>,
+    /// Sequence number of the last acknowledged keep-alive message.
+    acks: watch::Receiver<Option<u32>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl App {
+    /// Connect to MCU and wait until it acknowledges the first keep-alive message.
+    pub async fn connect(ao: Uv) -> Self {
+        let (data_read, data_write) = split(open(config::DATA_CHANNEL_ID).await);
+        let (control_read, control_write) = split(open(config::CONTROL_CHANNEL_ID).await);
+        let (mut data_read, mut data_write) = (reader(data_read), writer(data_write));
+        let (mut control_read, mut control_write) = (reader(control_read), writer(control_write));
+
+        send(&mut data_write, proto::AppMsgInitHello).await.unwrap();
+
+        let counters = Arc::new(Counters::default());
+        let paused = Arc::new(AtomicBool::new(false));
+        let (ack_send, mut acks) = watch::channel(None);
+        let mut connected = acks.clone();
+        let keep_alive = spawn({
+            let paused = paused.clone();
+            async move {
+                let mut seq = 0;
+                let mut hold = time::hold(time::now());
+                loop {
+                    if !paused.load(Ordering::Acquire) {
+                        send(&mut control_write, proto::AppMsgInitKeepAlive { seq })
+                            .await
+                            .unwrap();
+                        while *acks.borrow_and_update() != Some(seq) {
+                            acks.changed().await.unwrap();
+                        }
+                        seq = seq.wrapping_add(1);
+                    }
+                    hold = time::hold(hold.at() + KEEP_ALIVE_PERIOD);
+                    hold = reach(hold).await;
+                }
+            }
+        });
+        // AO is written by separate task, so that reading control channel never waits for MCU
+        // to take AO points.
+        let (request_send, mut requests) = unbounded_channel();
+        let control = spawn({
+            let counters = counters.clone();
+            async move {
+                loop {
+                    match control_read.read_message().await.unwrap().as_ref() {
+                        McuMsgRef::KeepAliveAck { seq } => {
+                            ack_send.send_replace(Some(*seq));
+                        }
+                        McuMsgRef::StateSync { .. } => {
+                            counters.state_syncs.fetch_add(1, Ordering::Relaxed);
+                        }
+                        McuMsgRef::StatsCounters {
+                            samples,
+                            crc_errors,
+                            ..
+                        } => {
+                            counters
+                                .mcu_samples
+                                .store(*samples as usize, Ordering::Relaxed);
+                            counters
+                                .mcu_crc_errors
+                                .store(*crc_errors as usize, Ordering::Relaxed);
+                        }
+                        McuMsgRef::AoRequest { count } => {
+                            counters.ao_requests.fetch_add(1, Ordering::Relaxed);
+                            request_send.send(*count as usize).unwrap();
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        });
+        let ao_write = spawn(async move {
+            while let Some(count) = requests.recv().await {
+                let mut remaining = count;
+                while remaining > 0 {
+                    let len = remaining.min(proto::AO_MSG_MAX_POINTS);
+                    let mut msg = data_write
+                        .alloc_message()
+                        .new_in_place(proto::AppMsgInitAoData {
+                            points: flat_vec![],
+                        })
+                        .unwrap();
+                    if let AppMsgMut::AoData { points } = msg.as_mut() {
+                        for _ in 0..len {
+                            points.push(Point::from_uv(ao)).unwrap();
+                        }
+                    }
+                    msg.write().await.unwrap();
+                    remaining -= len;
+                }
+            }
+        });
+        let data = spawn({
+            let counters = counters.clone();
+            async move {
+                loop {
+                    if let McuMsgRef::AiData { .. } =
+                        data_read.read_message().await.unwrap().as_ref()
+                    {
+                        counters.ai_data.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        });
+
+        while connected.borrow_and_update().is_none() {
+            connected.changed().await.unwrap();
+        }
+        Self {
+            counters,
+            paused,
+            acks: connected,
+            tasks: vec![keep_alive, control, ao_write, data],
+        }
+    }
+
+    /// Stop or resume sending keep-alive messages.
+    pub fn pause(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Release);
+    }
+
+    /// Wait until MCU acknowledges the next keep-alive message.
+    pub async fn acknowledged(&self) {
+        let mut acks = self.acks.clone();
+        acks.borrow_and_update();
+        acks.changed().await.unwrap();
+    }
+}
+
+impl Drop for App {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
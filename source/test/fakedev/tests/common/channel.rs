@@ -0,0 +1,9 @@
+//! IOC channel code. IOC is built as `cdylib` only, so sources are included directly.
+
+#[path = "../../../../app/user/src/channel/framed.rs"]
+mod framed;
+#[path = "../../../../app/user/src/channel/msg.rs"]
+mod msg;
+
+pub use framed::{FramedRead, FramedWrite};
+pub use msg::{MsgRead, MsgReader, MsgWrite, MsgWriter};
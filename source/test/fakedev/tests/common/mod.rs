@@ -0,0 +1,224 @@
+//! Fixtures shared by fakedev test suites.
+//!
+//! Each suite is a separate test binary including this module, so not every item is used by each of them.
+#![allow(dead_code)]
+
+pub mod app;
+mod channel;
+
+use common::{
+    config::{self, AI_COUNT, SAMPLE_PERIOD},
+    values::{AtomicUv, Uv},
+};
+use fakedev::{generator::spawn_plant, plant::Plant, run, FaultPlan, Faults, Skifio};
+use mcu::{tasks::STATISTICS, time};
+use std::{
+    env,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+use tokio::{
+    runtime::{self, Runtime},
+    sync::Mutex,
+    task::spawn_blocking,
+    time::timeout,
+};
+
+/// AO value written by IOC.
+pub const AO_VALUE: Uv = 1_000_000;
+
+/// Make fake MCU use its own channels, so that it doesn't interfere with MCU and IOC
+/// that may be running for other tests.
+///
+/// Must be called before MCU is started.
+pub fn isolate_channels(port_offset: u16, name: &str) {
+    env::set_var(
+        config::CHANNEL_PORT_ENV,
+        (config::CHANNEL_PORT + port_offset).to_string(),
+    );
+    env::set_var(config::CHANNEL_SOCKET_ENV, format!("/tmp/tornado-{}", name));
+}
+
+/// Fake MCU shared by test cases of a suite.
+pub struct Mcu {
+    pub runtime: Runtime,
+    /// Test cases share single MCU, so they are run one at a time.
+    lock: Mutex<()>,
+}
+
+impl Mcu {
+    /// Start MCU, its SkifIO board emulator is returned to be driven by caller.
+    pub fn start() -> (Self, Skifio) {
+        let runtime = runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let skifio = {
+            let _guard = runtime.enter();
+            run()
+        };
+        let mcu = Self {
+            runtime,
+            lock: Mutex::new(()),
+        };
+        (mcu, skifio)
+    }
+
+    /// Run `case` to completion while no other case is running.
+    pub fn run_case<F: Future>(&self, case: F) -> F::Output {
+        self.runtime.block_on(async {
+            let _guard = self.lock.lock().await;
+            case.await
+        })
+    }
+}
+
+/// Print MCU statistics to help investigate failed case.
+pub fn print_statistics() {
+    println!("Statistics: {}", STATISTICS.as_ref());
+}
+
+/// Wait until MCU clock reaches moment of `hold`, see [`time::Hold::wait`].
+pub async fn reach(hold: time::Hold) -> time::Hold {
+    spawn_blocking(move || {
+        hold.wait();
+        hold
+    })
+    .await
+    .unwrap()
+}
+
+/// What the SkifIO board has seen, updated by [`Probe`].
+#[derive(Default)]
+pub struct ProbeState {
+    /// Last AO value applied to SkifIO board.
+    ao: AtomicUv,
+    /// Number of samples transferred by SkifIO board.
+    samples: AtomicUsize,
+    /// Number of samples with AO value the probe is looking for.
+    played: AtomicUsize,
+    /// Number of samples at which virtual time was unexpected.
+    skews: AtomicUsize,
+}
+
+/// Applies AO to all AIs and records it.
+///
+/// Counts samples with `played` AO value. If MCU runs in virtual time then also checks that it
+/// advances by one sample period per sample.
+pub struct Probe {
+    state: Arc<ProbeState>,
+    played: Uv,
+    samples: u32,
+}
+
+impl Probe {
+    pub fn new(state: Arc<ProbeState>, played: Uv) -> Self {
+        Self {
+            state,
+            played,
+            samples: 0,
+        }
+    }
+}
+
+impl Plant for Probe {
+    fn step(&mut self, ao: Uv) -> [Uv; AI_COUNT] {
+        self.samples += 1;
+        if time::is_virtual() && time::now() != SAMPLE_PERIOD * self.samples {
+            self.state.skews.fetch_add(1, Ordering::Relaxed);
+        }
+        if ao == self.played {
+            self.state.played.fetch_add(1, Ordering::Relaxed);
+        }
+        self.state.ao.store(ao, Ordering::Relaxed);
+        self.state.samples.fetch_add(1, Ordering::Relaxed);
+        [ao; AI_COUNT]
+    }
+}
+
+/// Parameters of suite fixture.
+pub struct Setup {
+    /// Offset of channel ports and name of channel sockets, see [`isolate_channels`].
+    pub port_offset: u16,
+    pub name: &'static str,
+    /// Run MCU in virtual time.
+    pub virtual_time: bool,
+    /// Real time limit of a single case.
+    pub case_timeout: Duration,
+}
+
+/// Fake MCU with SkifIO board connected to [`Probe`] looking for [`AO_VALUE`].
+pub struct Fixture {
+    pub mcu: Mcu,
+    probe: Arc<ProbeState>,
+    pub faults: Faults,
+}
+
+impl Setup {
+    /// Get fixture of the suite, it is started on the first call.
+    ///
+    /// Fixture is a single static of test binary, so a suite must always use the same setup.
+    pub fn fixture(&self) -> &'static Fixture {
+        static FIXTURE: OnceLock<Fixture> = OnceLock::new();
+        FIXTURE.get_or_init(|| {
+            isolate_channels(self.port_offset, self.name);
+            if self.virtual_time {
+                time::set_virtual();
+            }
+
+            let (mcu, skifio) = Mcu::start();
+            let probe = Arc::new(ProbeState::default());
+            let faults = skifio.faults.clone();
+            {
+                let _guard = mcu.runtime.enter();
+                spawn_plant(skifio, Probe::new(probe.clone(), AO_VALUE));
+            }
+            Fixture { mcu, probe, faults }
+        })
+    }
+
+    /// Run `case` on suite fixture, it fails if it doesn't complete in time.
+    pub fn run_case<F: FnOnce(&'static Fixture) -> R, R: Future<Output = ()>>(&self, case: F) {
+        let fixture = self.fixture();
+        fixture.mcu.run_case(async {
+            if timeout(self.case_timeout, case(fixture)).await.is_err() {
+                print_statistics();
+                panic!("Timed out after {:?}", self.case_timeout);
+            }
+        });
+    }
+}
+
+impl Fixture {
+    pub fn ao(&self) -> Uv {
+        self.probe.ao.load(Ordering::Relaxed)
+    }
+    pub fn samples(&self) -> usize {
+        self.probe.samples.load(Ordering::Relaxed)
+    }
+    pub fn played(&self) -> usize {
+        self.probe.played.load(Ordering::Relaxed)
+    }
+    pub fn skews(&self) -> usize {
+        self.probe.skews.load(Ordering::Relaxed)
+    }
+
+    /// Inject faults until returned guard is dropped.
+    pub fn inject(&self, plan: FaultPlan) -> Injection<'_> {
+        self.faults.set_plan(plan);
+        Injection(&self.faults)
+    }
+}
+
+/// Restores default fault plan on drop, so that failed case doesn't affect others.
+pub struct Injection<'a>(&'a Faults);
+
+impl Drop for Injection<'_> {
+    fn drop(&mut self) {
+        self.0.set_plan(FaultPlan::default());
+    }
+}
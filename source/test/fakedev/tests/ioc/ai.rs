@@ -0,0 +1,125 @@
+use crate::{
+    fixture::{drain_ao, run_case, user_sample_intr, Context},
+    scale,
+};
+use approx::assert_abs_diff_eq;
+use common::{
+    config::AI_COUNT,
+    values::{try_volt_to_uv, Uv, VOLT_EPS},
+};
+use futures::{future::join_all, join, FutureExt, StreamExt};
+use std::{f64::consts::PI, iter::repeat_with};
+use tokio::{
+    sync::oneshot::{self, error::TryRecvError},
+    task::{spawn, yield_now},
+};
+
+const ATTEMPTS: usize = 80;
+
+fn is_zero(xs: &[f64; AI_COUNT]) -> bool {
+    xs.iter().all(|x| x.abs() < VOLT_EPS)
+}
+
+#[test]
+fn waveforms() {
+    run_case("ai::waveforms", |mut cx: Context| async move {
+        let _drain = drain_ao(&cx.device);
+        let len = cx
+            .epics
+            .ais
+            .iter()
+            .map(|adc| adc.waveform.element_count().unwrap())
+            .fold(None, |a, x| {
+                if let Some(y) = a {
+                    assert_eq!(x, y);
+                }
+                Some(x)
+            })
+            .unwrap();
+        let total_len = len * ATTEMPTS;
+        let data = (0..total_len)
+            .map(move |i| i as f64 / (total_len - 1) as f64)
+            .map(move |x| {
+                let mut k = 0;
+                [(); AI_COUNT].map(|()| {
+                    let y = scale((2.0 * PI * (k + 1) as f64 * x * ATTEMPTS as f64).sin()) * x;
+                    k += 1;
+                    y
+                })
+            });
+
+        let (done_send, mut done_recv) = oneshot::channel::<()>();
+        let prod = spawn({
+            let data = data.clone();
+            let device = cx.device.ais.clone();
+            async move {
+                let device = device.lock_owned().await;
+                for xs in data {
+                    let adcs = xs.map(|x| try_volt_to_uv(x).unwrap());
+                    device.send(adcs).await.unwrap();
+                    unsafe { user_sample_intr() };
+                }
+                // Keep sampling zeros until the last waveform is published.
+                while let Err(TryRecvError::Empty) = done_recv.try_recv() {
+                    device.send([Uv::default(); AI_COUNT]).await.unwrap();
+                    unsafe { user_sample_intr() };
+                    yield_now().await;
+                }
+            }
+        })
+        .map(Result::unwrap);
+
+        let cons = spawn(async move {
+            let mut arrays = cx
+                .epics
+                .ais
+                .iter_mut()
+                .map(|adc| Box::pin(adc.waveform.subscribe_vec()))
+                .collect::<Vec<_>>();
+            // Fixture feeds zeros between test cases, so leading zeros are skipped.
+            let mut expected = data.skip_while(is_zero).peekable();
+            let mut started = false;
+            while expected.peek().is_some() {
+                let wfs = join_all(
+                    arrays
+                        .iter_mut()
+                        .map(|sub| async { sub.next().await.unwrap().unwrap() }),
+                )
+                .await;
+                let points = {
+                    let mut iters = wfs.into_iter().map(|wf| wf.into_iter()).collect::<Vec<_>>();
+                    repeat_with(move || {
+                        let mut res = [None; AI_COUNT];
+                        for (i, a) in iters.iter_mut().enumerate() {
+                            res[i] = a.next();
+                        }
+                        res[0]?;
+                        Some(res.map(|x| x.unwrap()))
+                    })
+                    .take_while(|x| x.is_some())
+                    .map(|x| x.unwrap())
+                    .collect::<Vec<_>>()
+                };
+                for xs in points {
+                    if !started {
+                        if is_zero(&xs) {
+                            continue;
+                        }
+                        started = true;
+                    }
+                    let ys = match expected.next() {
+                        Some(ys) => ys,
+                        None => break,
+                    };
+                    xs.into_iter()
+                        .zip(ys)
+                        .for_each(|(x, y)| assert_abs_diff_eq!(x, y, epsilon = VOLT_EPS));
+                }
+            }
+            done_send.send(()).unwrap();
+        })
+        .map(Result::unwrap);
+
+        join!(prod, cons);
+    });
+}
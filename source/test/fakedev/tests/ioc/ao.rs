@@ -0,0 +1,97 @@
+use crate::{
+    fixture::{clock, run_case, wait_ao_ready, Context},
+    scale,
+};
+use approx::assert_abs_diff_eq;
+use common::values::{uv_to_volt, Uv, VOLT_EPS};
+use epics_ca::types::EpicsEnum;
+use futures::{join, FutureExt};
+use std::f64::consts::PI;
+use tokio::{sync::mpsc::Receiver, task::spawn};
+
+const ATTEMPTS: usize = 64;
+const CYCLIC_ATTEMPTS: usize = 16;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < VOLT_EPS
+}
+
+/// Check that device receives `expected` values.
+///
+/// Leading zeros are skipped because fixture outputs zeros between test cases.
+async fn check_output(device: &mut Receiver<Uv>, expected: impl Iterator<Item = f64>) {
+    let mut expected = expected.skip_while(|x| is_zero(*x));
+    let first = loop {
+        let value = uv_to_volt(device.recv().await.unwrap());
+        if !is_zero(value) {
+            break value;
+        }
+    };
+    assert_abs_diff_eq!(first, expected.next().unwrap(), epsilon = VOLT_EPS);
+    for x in expected {
+        assert_abs_diff_eq!(
+            uv_to_volt(device.recv().await.unwrap()),
+            x,
+            epsilon = VOLT_EPS
+        );
+    }
+}
+
+#[test]
+fn one_shot() {
+    run_case("ao::one_shot", |mut cx: Context| async move {
+        let _clock = clock(&cx.device);
+        let len = cx.epics.ao.waveform.element_count().unwrap();
+        let data = (0..ATTEMPTS).map(move |j| {
+            (0..len)
+                .map(move |i| i as f64 / (len - 1) as f64)
+                .map(move |x| scale((2.0 * PI * (j + 1) as f64 * x).sin()))
+        });
+
+        cx.epics.ao.cyclic.put(EpicsEnum(1)).unwrap().await.unwrap();
+
+        let prod = spawn({
+            let data = data.clone();
+            let mut epics = cx.epics.ao;
+            async move {
+                for wf in data {
+                    wait_ao_ready(&mut epics).await;
+                    let wf = wf.collect::<Vec<_>>();
+                    epics.waveform.put_ref(&wf).unwrap().await.unwrap();
+                }
+            }
+        })
+        .map(Result::unwrap);
+
+        let cons = spawn({
+            let device = cx.device.ao.clone();
+            async move {
+                let mut device = device.lock_owned().await;
+                check_output(&mut device, data.flatten()).await;
+            }
+        })
+        .map(Result::unwrap);
+
+        join!(prod, cons);
+    });
+}
+
+#[test]
+fn cyclic() {
+    run_case("ao::cyclic", |mut cx: Context| async move {
+        let _clock = clock(&cx.device);
+        let len = cx.epics.ao.waveform.element_count().unwrap();
+        let data = (0..len)
+            .map(move |i| i as f64 / (len - 1) as f64)
+            .map(move |x| x * scale((2.0 * PI * x).sin()))
+            .collect::<Vec<_>>();
+
+        // Fixture leaves AO in cyclic mode.
+        wait_ao_ready(&mut cx.epics.ao).await;
+        cx.epics.ao.waveform.put_ref(&data).unwrap().await.unwrap();
+
+        let mut device = cx.device.ao.lock().await;
+        let expected = data.into_iter().cycle().take(len * CYCLIC_ATTEMPTS);
+        check_output(&mut device, expected).await;
+    });
+}
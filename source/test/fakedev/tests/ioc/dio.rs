@@ -0,0 +1,56 @@
+use crate::fixture::{clock, drain_ao, run_case, Context};
+use common::values::{Di, Do};
+use futures::StreamExt;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro128PlusPlus as SomeRng;
+
+const ATTEMPTS: usize = 64;
+
+#[test]
+fn output() {
+    run_case("dio::output", |mut cx: Context| async move {
+        let (_clock, _drain) = (clock(&cx.device), drain_ao(&cx.device));
+        let mut device = cx.device.do_.lock().await;
+        let mut rng = SomeRng::seed_from_u64(0xdeadbeef);
+        // Fixture clears all DO bits.
+        let mut value = 0;
+        for _ in 0..ATTEMPTS {
+            let i = rng.gen_range(0..Do::SIZE);
+            value ^= 1 << i;
+            cx.epics.do_[i]
+                .put((value >> i) & 1)
+                .unwrap()
+                .await
+                .unwrap();
+            // DO is written to SkifIO only on change.
+            assert_eq!(value, device.recv().await.unwrap().into());
+        }
+    });
+}
+
+#[test]
+fn input() {
+    run_case("dio::input", |mut cx: Context| async move {
+        let (_clock, _drain) = (clock(&cx.device), drain_ao(&cx.device));
+        let device = cx.device.di.lock().await;
+        let mut rng = SomeRng::seed_from_u64(0xdeadbeef);
+        let mut value = 0;
+        let mut monitors = cx
+            .epics
+            .di
+            .iter_mut()
+            .map(|chan| Box::pin(chan.subscribe()))
+            .collect::<Vec<_>>();
+        device.send(Di::default()).await.unwrap();
+        // Monitors start with values left by previous cases.
+        for mon in monitors.iter_mut() {
+            while mon.next().await.unwrap().unwrap() != 0 {}
+        }
+        for _ in 0..ATTEMPTS {
+            let i = rng.gen_range(0..Di::SIZE);
+            value ^= 1 << i;
+            device.send(Di::try_from(value).unwrap()).await.unwrap();
+            assert_eq!(monitors[i].next().await.unwrap().unwrap(), (value >> i) & 1);
+        }
+    });
+}
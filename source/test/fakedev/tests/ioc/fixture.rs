@@ -0,0 +1,249 @@
+use crate::shared::{print_statistics, Mcu};
+use common::{
+    config::AI_COUNT,
+    values::{Di, Do, Uv},
+};
+use epics_ca::{self as ca, types::EpicsEnum};
+use fakedev::{epics, Epics};
+use futures::{pin_mut, StreamExt};
+use std::{
+    any::Any,
+    env,
+    fs::{File, OpenOptions},
+    future::Future,
+    io::Write,
+    panic::resume_unwind,
+    sync::{Arc, Mutex as SyncMutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{
+        mpsc::{Receiver, Sender},
+        Mutex,
+    },
+    task::{spawn, yield_now, JoinHandle},
+    time::timeout,
+};
+
+extern "C" {
+    pub fn user_sample_intr();
+}
+
+const PREFIX: &str = "tornado0:";
+
+/// Path to file where results of test cases are appended as JSON lines.
+const REPORT_ENV: &str = "TORNADO_TEST_REPORT";
+
+/// Maximum duration of a single test case including setup.
+const CASE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Device side of emulated SkifIO board.
+///
+/// Each part is locked separately, so that a test case can hand some of them to background tasks.
+#[derive(Clone)]
+pub struct Device {
+    pub ao: Arc<Mutex<Receiver<Uv>>>,
+    pub ais: Arc<Mutex<Sender<[Uv; AI_COUNT]>>>,
+    pub do_: Arc<Mutex<Receiver<Do>>>,
+    pub di: Arc<Mutex<Sender<Di>>>,
+}
+
+/// Everything a test case has access to.
+pub struct Context {
+    pub epics: Epics,
+    pub device: Device,
+}
+
+struct Fixture {
+    /// Test cases also share single IOC instance.
+    mcu: Mcu,
+    ca: ca::Context,
+    device: Device,
+    report: Option<SyncMutex<File>>,
+}
+
+impl Fixture {
+    fn get() -> &'static Self {
+        static FIXTURE: OnceLock<Fixture> = OnceLock::new();
+        FIXTURE.get_or_init(|| {
+            let (mcu, skifio) = Mcu::start();
+            let report = env::var_os(REPORT_ENV).map(|path| {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .unwrap();
+                SyncMutex::new(file)
+            });
+            Self {
+                mcu,
+                ca: ca::Context::new().unwrap(),
+                device: Device {
+                    ao: Arc::new(Mutex::new(skifio.ao)),
+                    ais: Arc::new(Mutex::new(skifio.ais)),
+                    do_: Arc::new(Mutex::new(skifio.do_)),
+                    di: Arc::new(Mutex::new(skifio.di)),
+                },
+                report,
+            }
+        })
+    }
+
+    fn report(&self, name: &str, message: Option<&str>, elapsed: Duration) {
+        let report = match &self.report {
+            Some(file) => file,
+            None => return,
+        };
+        let line = format!(
+            "{{\"name\":\"{}\",\"result\":\"{}\",\"duration\":{:.3},\"message\":{}}}\n",
+            escape(name),
+            if message.is_some() { "failed" } else { "ok" },
+            elapsed.as_secs_f64(),
+            match message {
+                Some(text) => format!("\"{}\"", escape(text)),
+                None => "null".into(),
+            },
+        );
+        report.lock().unwrap().write_all(line.as_bytes()).unwrap();
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// Run test case `case` named `name` against shared fake MCU and IOC.
+///
+/// MCU is started on first call. Before each case EPICS channels are connected
+/// and device is brought to the initial state: cyclic zero AO and all DO bits cleared.
+pub fn run_case<F, R>(name: &str, case: F)
+where
+    F: FnOnce(Context) -> R + Send + 'static,
+    R: Future<Output = ()> + Send + 'static,
+{
+    let fixture = Fixture::get();
+    fixture.mcu.run_case(async {
+        let start = Instant::now();
+        let task = spawn(async move {
+            let mut epics = Epics::connect(&fixture.ca, PREFIX).await;
+            reset(&mut epics, &fixture.device).await;
+            case(Context {
+                epics,
+                device: fixture.device.clone(),
+            })
+            .await
+        });
+        let abort = task.abort_handle();
+        let result = timeout(CASE_TIMEOUT, task).await;
+        let elapsed = start.elapsed();
+        match result {
+            Ok(Ok(())) => fixture.report(name, None, elapsed),
+            Ok(Err(err)) => {
+                let payload = err.into_panic();
+                fixture.report(name, Some(panic_message(&*payload)), elapsed);
+                print_statistics();
+                resume_unwind(payload);
+            }
+            Err(_) => {
+                abort.abort();
+                let message = format!("Timed out after {:?}", CASE_TIMEOUT);
+                fixture.report(name, Some(&message), elapsed);
+                print_statistics();
+                panic!("{}", message);
+            }
+        }
+    });
+}
+
+/// Bring MCU and IOC to the initial state.
+async fn reset(epics: &mut Epics, device: &Device) {
+    let len = epics.ao.waveform.element_count().unwrap();
+    let _clock = clock(device);
+
+    epics.ao.cyclic.put(EpicsEnum(0)).unwrap().await.unwrap();
+    wait_ao_ready(&mut epics.ao).await;
+    epics
+        .ao
+        .waveform
+        .put_ref(&vec![0.0; len])
+        .unwrap()
+        .await
+        .unwrap();
+    for chan in epics.do_.iter_mut() {
+        chan.put(0).unwrap().await.unwrap();
+    }
+
+    // Leftovers of previous waveform cannot contain a whole waveform of zeros.
+    let mut ao = device.ao.lock().await;
+    let mut zeros = 0;
+    while zeros < len {
+        if ao.recv().await.unwrap() == 0 {
+            zeros += 1;
+        } else {
+            zeros = 0;
+        }
+    }
+    // DO is already written by this time.
+    let mut do_ = device.do_.lock().await;
+    while do_.try_recv().is_ok() {}
+}
+
+/// Wait until IOC requests next AO waveform.
+pub async fn wait_ao_ready(ao: &mut epics::Ao) {
+    let request = ao.ready.subscribe();
+    pin_mut!(request);
+    while request.next().await.unwrap().unwrap() == EpicsEnum(0) {}
+}
+
+/// Background task aborted on drop.
+pub struct Background(JoinHandle<()>);
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Feed zero AIs to MCU to keep its control loop running.
+///
+/// Samples are not paced by wall clock, MCU consumes them as fast as it can.
+pub fn clock(device: &Device) -> Background {
+    let ais = device.ais.clone();
+    Background(spawn(async move {
+        let ais = ais.lock_owned().await;
+        loop {
+            ais.send([Uv::default(); AI_COUNT]).await.unwrap();
+            unsafe { user_sample_intr() };
+            yield_now().await;
+        }
+    }))
+}
+
+/// Discard AO values written by MCU.
+pub fn drain_ao(device: &Device) -> Background {
+    let ao = device.ao.clone();
+    Background(spawn(async move {
+        let mut ao = ao.lock_owned().await;
+        while ao.recv().await.is_some() {}
+    }))
+}
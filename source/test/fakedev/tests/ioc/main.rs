@@ -0,0 +1,19 @@
+//! Tests of IOC and fake MCU working together.
+//!
+//! IOC must be running and accessible via Channel Access, so the suite is built only with `ioc` feature.
+//! Set `TORNADO_TEST_REPORT` to a file path to get results of test cases as JSON lines.
+
+#[path = "../common/mod.rs"]
+mod shared;
+
+mod ai;
+mod ao;
+mod dio;
+mod fixture;
+
+const VOLT_MAX: f64 = -10.0;
+const VOLT_MIN: f64 = 10.0;
+
+fn scale(x: f64) -> f64 {
+    (x + 1.0) / 2.0 * (VOLT_MAX - VOLT_MIN) + VOLT_MIN
+}
@@ -0,0 +1,224 @@
+//! Scenarios of IOC connection being lost and restored, SkifIO faults, and MCU task and counter monitoring.
+//!
+//! IOC side of MCU channels is emulated by the test itself, so IOC is not needed.
+
+#[path = "../common/mod.rs"]
+mod shared;
+
+use common::{
+    config::{KEEP_ALIVE_MAX_DELAY, SKIFIO_STATE_PERIOD},
+    protocol::TaskUsage,
+};
+use fakedev::FaultPlan;
+use mcu::tasks::{usage, STATISTICS};
+use shared::{app::App, Fixture, Setup, AO_VALUE};
+use std::{sync::atomic::Ordering, time::Duration};
+use tokio::time::sleep;
+
+const SETUP: Setup = Setup {
+    port_offset: 100,
+    name: "link",
+    virtual_time: false,
+    case_timeout: Duration::from_secs(30),
+};
+
+/// Time to wait for MCU to react on connection change.
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// Probability of injected SkifIO fault per sample.
+const FAULT_RATE: f64 = 0.01;
+
+/// Max number of samples that may be counted by emulator but not yet by MCU statistics.
+const MAX_IN_FLIGHT: usize = 10;
+
+/// Check that MCU plays AO from `app` and streams AI to it.
+async fn assert_streaming(fixture: &Fixture, app: &App) {
+    sleep(SETTLE).await;
+    assert_eq!(fixture.ao(), AO_VALUE);
+    let (ai_data, ao_requests) = (
+        app.counters.ai_data.load(Ordering::Relaxed),
+        app.counters.ao_requests.load(Ordering::Relaxed),
+    );
+    sleep(SETTLE).await;
+    assert!(app.counters.ai_data.load(Ordering::Relaxed) > ai_data);
+    assert!(app.counters.ao_requests.load(Ordering::Relaxed) > ao_requests);
+}
+
+/// Check that MCU has disabled AO but keeps sampling and discarding AI.
+async fn assert_dropped(fixture: &Fixture, drops: usize) {
+    sleep(SETTLE).await;
+    assert_eq!(STATISTICS.ioc_drop_count(), drops);
+    assert_eq!(fixture.ao(), 0);
+    let samples = fixture.samples();
+    sleep(SETTLE).await;
+    assert!(fixture.samples() > samples);
+}
+
+#[test]
+fn keep_alive_timeout() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app).await;
+        let drops = STATISTICS.ioc_drop_count();
+
+        app.pause(true);
+        sleep(KEEP_ALIVE_MAX_DELAY).await;
+        assert_dropped(fixture, drops + 1).await;
+        let ai_data = app.counters.ai_data.load(Ordering::Relaxed);
+        sleep(SETTLE).await;
+        assert_eq!(app.counters.ai_data.load(Ordering::Relaxed), ai_data);
+
+        let state_syncs = app.counters.state_syncs.load(Ordering::Relaxed);
+        app.pause(false);
+        assert_streaming(fixture, &app).await;
+        assert!(app.counters.state_syncs.load(Ordering::Relaxed) > state_syncs);
+        assert_eq!(STATISTICS.ioc_drop_count(), drops + 1);
+    });
+}
+
+#[test]
+fn reconnect() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app).await;
+        let drops = STATISTICS.ioc_drop_count();
+
+        drop(app);
+        assert_dropped(fixture, drops + 1).await;
+
+        let app = App::connect(2 * AO_VALUE).await;
+        sleep(SETTLE).await;
+        assert_eq!(app.counters.state_syncs.load(Ordering::Relaxed), 1);
+        // Points of previous connection left in AO buffer are played first.
+        loop {
+            match fixture.ao() {
+                value if value == 2 * AO_VALUE => break,
+                0 | AO_VALUE => sleep(SETTLE).await,
+                value => panic!("Unexpected AO value: {}", value),
+            }
+        }
+        let ai_data = app.counters.ai_data.load(Ordering::Relaxed);
+        sleep(SETTLE).await;
+        assert!(app.counters.ai_data.load(Ordering::Relaxed) > ai_data);
+        assert_eq!(STATISTICS.ioc_drop_count(), drops + 1);
+    });
+}
+
+#[test]
+fn task_usage() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app).await;
+
+        let control = |tasks: &[TaskUsage]| {
+            tasks
+                .iter()
+                .find(|task| task.name.starts_with(b"control\0"))
+                .copied()
+                .expect("Control task is not reported")
+        };
+        let before = control(&usage::task_usage());
+        sleep(SETTLE).await;
+        let after = control(&usage::task_usage());
+        assert!(after.run_time.wrapping_sub(before.run_time) > 0);
+        assert_eq!(after.stack_free, u32::MAX);
+    });
+}
+
+#[test]
+fn stats_counters() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app).await;
+
+        sleep(2 * SKIFIO_STATE_PERIOD).await;
+        let reported = app.counters.mcu_samples.load(Ordering::Relaxed);
+        assert!(reported > 0);
+        assert!(reported as u32 <= STATISTICS.sample_count() as u32);
+    });
+}
+
+#[test]
+fn crc_errors() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app).await;
+        let drops = STATISTICS.ioc_drop_count();
+        let injected = &fixture.faults.injected.crc_errors;
+        let (counted, injected_before) = (
+            STATISTICS.crc_error_count(),
+            injected.load(Ordering::Relaxed),
+        );
+
+        let injection = fixture.inject(FaultPlan {
+            crc_error_rate: FAULT_RATE,
+            ..FaultPlan::default()
+        });
+        sleep(SETTLE).await;
+        drop(injection);
+        sleep(SETTLE).await;
+
+        let injected = injected.load(Ordering::Relaxed) - injected_before;
+        assert!(injected > 0);
+        assert_eq!(STATISTICS.crc_error_count() - counted, injected);
+
+        // Sample with CRC error is replaced by the previous one, so IOC keeps streaming.
+        assert_streaming(fixture, &app).await;
+        assert_eq!(STATISTICS.ioc_drop_count(), drops);
+        sleep(2 * SKIFIO_STATE_PERIOD).await;
+        assert_eq!(
+            app.counters.mcu_crc_errors.load(Ordering::Relaxed),
+            STATISTICS.crc_error_count()
+        );
+    });
+}
+
+#[test]
+fn missed_syncs() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app).await;
+        let drops = STATISTICS.ioc_drop_count();
+        let injected = &fixture.faults.injected.missed_syncs;
+        let (transferred, counted, injected_before) = (
+            fixture.samples(),
+            STATISTICS.sample_count(),
+            injected.load(Ordering::Relaxed),
+        );
+
+        let injection = fixture.inject(FaultPlan {
+            missed_sync_rate: FAULT_RATE,
+            ..FaultPlan::default()
+        });
+        sleep(SETTLE).await;
+        drop(injection);
+        sleep(SETTLE).await;
+
+        // Missed samples reach the board but are not counted by MCU.
+        let (transferred, counted, injected) = (
+            fixture.samples() - transferred,
+            STATISTICS.sample_count() - counted,
+            injected.load(Ordering::Relaxed) - injected_before,
+        );
+        assert!(injected > 0);
+        assert!(
+            (counted + injected).abs_diff(transferred) <= MAX_IN_FLIGHT,
+            "transferred: {}, counted: {}, missed: {}",
+            transferred,
+            counted,
+            injected
+        );
+
+        assert_streaming(fixture, &app).await;
+        assert_eq!(STATISTICS.ioc_drop_count(), drops);
+    });
+}
+
+#[test]
+#[should_panic(expected = "CRC error rate must be in 0.0..=1.0")]
+fn invalid_fault_rate() {
+    SETUP.fixture().faults.set_plan(FaultPlan {
+        crc_error_rate: 1.5,
+        ..FaultPlan::default()
+    });
+}
@@ -0,0 +1,117 @@
+//! Rates computed by IOC from MCU event counters.
+
+use approx::assert_abs_diff_eq;
+use common::rates::RateHistory;
+use std::time::Duration;
+
+const SECOND_US: u32 = 1_000_000;
+
+const MAX_WINDOW: Duration = Duration::from_secs(10);
+
+const RATE_EPS: f64 = 1e-9;
+
+fn assert_rates(history: &RateHistory<2>, window: Duration, expected: [f64; 2]) {
+    let rates = history.rates(window).expect("No rates");
+    for (rate, value) in rates.into_iter().zip(expected) {
+        assert_abs_diff_eq!(rate, value, epsilon = RATE_EPS);
+    }
+}
+
+#[test]
+fn windows() {
+    let mut history = RateHistory::new(MAX_WINDOW);
+    // First counter increments at 100 Hz, second one is idle for 15 s and then increments at 40 Hz.
+    for t in 0..=20 {
+        let idle = t.min(15);
+        history.push(t * SECOND_US, [100 * t, 40 * (t - idle)]);
+    }
+    assert_rates(&history, Duration::from_secs(1), [100.0, 40.0]);
+    assert_rates(&history, Duration::from_secs(5), [100.0, 40.0]);
+    assert_rates(&history, Duration::from_secs(10), [100.0, 20.0]);
+    // Older samples are dropped, so longer window is limited by `MAX_WINDOW`.
+    assert_rates(&history, Duration::from_secs(20), [100.0, 20.0]);
+}
+
+#[test]
+fn short_history() {
+    let mut history = RateHistory::new(MAX_WINDOW);
+    assert!(history.rates(Duration::from_secs(1)).is_none());
+    history.push(0, [0, 0]);
+    assert!(history.rates(Duration::from_secs(1)).is_none());
+    history.push(SECOND_US / 2, [50, 0]);
+    assert_rates(&history, Duration::from_secs(5), [100.0, 0.0]);
+}
+
+#[test]
+fn mcu_time() {
+    let mut history = RateHistory::new(MAX_WINDOW);
+    // Counters arrive irregularly, rates depend on MCU time only.
+    for (time_us, count) in [(0, 0), (250_000, 250), (300_000, 300), (1_000_000, 1000)] {
+        history.push(time_us, [count, 2 * count]);
+        if time_us != 0 {
+            assert_rates(&history, Duration::from_secs(1), [1000.0, 2000.0]);
+        }
+    }
+}
+
+#[test]
+fn time_wraparound() {
+    let mut history = RateHistory::new(MAX_WINDOW);
+    let start = u32::MAX - SECOND_US / 2;
+    for t in 0..=5 {
+        history.push(start.wrapping_add(t * SECOND_US), [10 * t, 0]);
+    }
+    assert_rates(&history, Duration::from_secs(1), [10.0, 0.0]);
+    assert_rates(&history, Duration::from_secs(5), [10.0, 0.0]);
+}
+
+#[test]
+fn reset() {
+    let mut history = RateHistory::new(MAX_WINDOW);
+    history.push(0, [1000, 10]);
+    history.push(SECOND_US, [2000, 20]);
+    assert_rates(&history, Duration::from_secs(1), [1000.0, 10.0]);
+
+    // Statistics are reset, rates are computed from scratch.
+    history.push(2 * SECOND_US, [50, 30]);
+    assert!(history.rates(Duration::from_secs(1)).is_none());
+    history.push(3 * SECOND_US, [150, 40]);
+    assert_rates(&history, Duration::from_secs(5), [100.0, 10.0]);
+}
+
+#[test]
+fn counter_wraparound() {
+    let mut history = RateHistory::new(MAX_WINDOW);
+    history.push(0, [u32::MAX - 10, 0]);
+    history.push(SECOND_US, [u32::MAX, 1]);
+    assert_rates(&history, Duration::from_secs(1), [10.0, 1.0]);
+
+    // Wrapped counter looks like reset, history is cleared instead of producing huge rate.
+    history.push(2 * SECOND_US, [9, 2]);
+    assert!(history.rates(Duration::from_secs(1)).is_none());
+    history.push(3 * SECOND_US, [19, 3]);
+    assert_rates(&history, Duration::from_secs(1), [10.0, 1.0]);
+}
+
+#[test]
+fn late_counters() {
+    let mut history = RateHistory::new(MAX_WINDOW);
+    // Nothing to extend yet.
+    history.stall(Duration::from_secs(2));
+    assert!(history.rates(Duration::from_secs(1)).is_none());
+
+    for t in 0..=4 {
+        history.push(t * SECOND_US, [100 * t, 0]);
+    }
+    // Counters are considered unchanged while they are late, so rates fall.
+    history.stall(Duration::from_secs(2));
+    assert_rates(&history, Duration::from_secs(1), [0.0, 0.0]);
+    assert_rates(&history, Duration::from_secs(4), [50.0, 0.0]);
+    history.stall(Duration::from_secs(2));
+    assert_rates(&history, Duration::from_secs(4), [0.0, 0.0]);
+
+    // Counters that finally arrive replace guessed ones.
+    history.push(5 * SECOND_US, [500, 0]);
+    assert_rates(&history, Duration::from_secs(1), [100.0, 0.0]);
+    assert_rates(&history, Duration::from_secs(5), [100.0, 0.0]);
+}
@@ -0,0 +1,155 @@
+//! Simulation of MCU in virtual time.
+//!
+//! IOC side of MCU channels is emulated by the test itself, so IOC is not needed.
+//! Emulated IOC acts at exact moments of virtual time, so a case gives the same results on every run
+//! and an hour of MCU time is simulated in seconds.
+
+#[path = "../common/mod.rs"]
+mod shared;
+
+use common::config::{KEEP_ALIVE_MAX_DELAY, KEEP_ALIVE_PERIOD, LOOP_HIST_BOUNDS_US, SAMPLE_PERIOD};
+use fakedev::FaultPlan;
+use mcu::{tasks::STATISTICS, time};
+use shared::{app::App, reach, Fixture, Setup, AO_VALUE};
+use std::{sync::atomic::Ordering, time::Duration};
+
+const SETUP: Setup = Setup {
+    port_offset: 200,
+    name: "sim",
+    virtual_time: true,
+    case_timeout: Duration::from_secs(60),
+};
+
+/// Number of samples in `duration` of virtual time.
+fn samples(duration: Duration) -> usize {
+    (duration.as_nanos() / SAMPLE_PERIOD.as_nanos()) as usize
+}
+
+/// Stop virtual time at the next sample, so that the sample in flight is completed
+/// with current settings.
+async fn stop() -> time::Hold {
+    reach(time::hold(time::now() + SAMPLE_PERIOD)).await
+}
+
+/// Let virtual time run for `duration` after moment of `hold`.
+async fn run_for(hold: time::Hold, duration: Duration) -> time::Hold {
+    let next = time::hold(hold.at() + duration);
+    drop(hold);
+    reach(next).await
+}
+
+/// Check that MCU plays AO from `app` on every sample for `duration` and streams AI to it.
+async fn assert_streaming(fixture: &Fixture, app: &App, duration: Duration) {
+    let drops = STATISTICS.ioc_drop_count();
+    let start = stop().await;
+    let (played, ai_data) = (
+        fixture.played(),
+        app.counters.ai_data.load(Ordering::Relaxed),
+    );
+    let (cycles, waits) = (
+        STATISTICS.cycle_time.counts(),
+        STATISTICS.wait_ready_time.counts(),
+    );
+
+    let _end = run_for(start, duration).await;
+    assert_eq!(fixture.played() - played, samples(duration));
+    assert_eq!(fixture.skews(), 0);
+    assert_eq!(STATISTICS.ioc_drop_count(), drops);
+    assert!(app.counters.ai_data.load(Ordering::Relaxed) > ai_data);
+
+    // Loop timing is measured in virtual time, so every cycle takes exactly one sample period
+    // and SkifIO is always ready immediately.
+    let period_bin =
+        LOOP_HIST_BOUNDS_US.partition_point(|&bound| bound as u128 <= SAMPLE_PERIOD.as_micros());
+    let cycles = STATISTICS
+        .cycle_time
+        .counts()
+        .into_iter()
+        .zip(cycles)
+        .map(|(after, before)| after - before)
+        .collect::<Vec<_>>();
+    assert_eq!(cycles[period_bin] as usize, samples(duration));
+    assert_eq!(cycles.iter().sum::<u32>() as usize, samples(duration));
+    let waits = STATISTICS
+        .wait_ready_time
+        .counts()
+        .into_iter()
+        .zip(waits)
+        .map(|(after, before)| after - before)
+        .collect::<Vec<_>>();
+    assert_eq!(waits[0] as usize, samples(duration));
+}
+
+#[test]
+fn virtual_time() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app, Duration::from_secs(20)).await;
+    });
+}
+
+#[test]
+fn one_hour() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        assert_streaming(fixture, &app, Duration::from_secs(60 * 60)).await;
+    });
+}
+
+#[test]
+fn keep_alive_loss() {
+    SETUP.run_case(|fixture| async move {
+        let app = App::connect(AO_VALUE).await;
+        let drops = STATISTICS.ioc_drop_count();
+
+        app.pause(true);
+        // Keep-alive timeout is measured in virtual time, so MCU notices lost IOC while it runs.
+        let mut hold = stop().await;
+        let start = hold.at();
+        while STATISTICS.ioc_drop_count() == drops {
+            assert!(
+                hold.at() - start <= 2 * KEEP_ALIVE_MAX_DELAY,
+                "IOC is not dropped, statistics: {}",
+                STATISTICS.as_ref()
+            );
+            hold = run_for(hold, KEEP_ALIVE_PERIOD).await;
+        }
+        assert!(hold.at() - start >= KEEP_ALIVE_MAX_DELAY - KEEP_ALIVE_PERIOD);
+        let hold = run_for(hold, KEEP_ALIVE_MAX_DELAY).await;
+        assert_eq!(STATISTICS.ioc_drop_count(), drops + 1);
+        assert_eq!(fixture.ao(), 0);
+        drop(hold);
+
+        app.pause(false);
+        app.acknowledged().await;
+        assert_streaming(fixture, &app, Duration::from_secs(1)).await;
+        assert_eq!(STATISTICS.ioc_drop_count(), drops + 1);
+    });
+}
+
+#[test]
+fn skifio_stall() {
+    SETUP.run_case(|fixture| async move {
+        const STALL: Duration = Duration::from_secs(3);
+        let app = App::connect(AO_VALUE).await;
+        let drops = STATISTICS.ioc_drop_count();
+
+        let injection = fixture.inject(FaultPlan {
+            missed_sync_rate: 1.0,
+            ..FaultPlan::default()
+        });
+        let missed = || fixture.faults.injected.missed_syncs.load(Ordering::Relaxed);
+        // Control loop waits for SkifIO and times out, but virtual time goes on with missed sync pulses.
+        let start = stop().await;
+        let (samples_before, missed_before) = (STATISTICS.sample_count(), missed());
+        let end = run_for(start, STALL).await;
+        assert_eq!(STATISTICS.sample_count(), samples_before);
+        assert_eq!(missed() - missed_before, samples(STALL));
+        // IOC keeps sending keep-alive messages in virtual time, so it is not dropped.
+        assert_eq!(STATISTICS.ioc_drop_count(), drops);
+        drop(injection);
+        drop(end);
+
+        assert_streaming(fixture, &app, Duration::from_secs(1)).await;
+    });
+}
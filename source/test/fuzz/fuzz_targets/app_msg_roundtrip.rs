@@ -0,0 +1,6 @@
+#![no_main]
+
+use fuzz::roundtrip::{self, AppMsgInput};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: AppMsgInput| roundtrip::app_msg(&input));
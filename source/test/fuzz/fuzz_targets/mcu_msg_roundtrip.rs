@@ -0,0 +1,6 @@
+#![no_main]
+
+use fuzz::roundtrip::{self, McuMsgInput};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: McuMsgInput| roundtrip::mcu_msg(&input));
@@ -0,0 +1,10 @@
+//! IOC channel code. IOC is built as `cdylib` only, so sources are included directly.
+#![allow(dead_code)]
+
+#[path = "../../../app/user/src/channel/framed.rs"]
+mod framed;
+#[path = "../../../app/user/src/channel/msg.rs"]
+mod msg;
+
+pub use framed::FramedRead;
+pub use msg::{MsgRead, MsgReader, MsgWrite, ReadError};
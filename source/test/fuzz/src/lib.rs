@@ -0,0 +1,175 @@
+//! Fuzzing of protocol parsing on both sides of IOC and MCU channels.
+//!
+//! Targets are run with `cargo fuzz run <target>` from this directory.
+//! Fake MCU channel reports every connection to stdout, so `-- -close_fd_mask=1` is useful for `mcu_reader`.
+
+pub mod channel;
+pub mod roundtrip;
+
+use channel::{FramedRead, MsgReader, ReadError};
+pub use common::buffer::Buffer;
+use common::{
+    config::MAX_MCU_MSG_LEN,
+    protocol::{AppMsg, AppMsgRef, McuMsg, McuMsgRef},
+    values::{Point, PointOpt},
+};
+use flatty::{prelude::*, traits::FlatBase};
+use futures::executor::block_on;
+use mcu::{
+    channel::{endpoints, Reader},
+    error::{Error, ErrorKind},
+};
+use std::{hint::black_box, io::Write, os::unix::net::UnixStream, sync::mpsc};
+
+/// Inputs longer than this are skipped to fit into socket buffer.
+const MAX_STREAM_LEN: usize = 0x10000;
+
+fn point_value(point: &Point) -> i64 {
+    match point.into_opt() {
+        PointOpt::Uv(value) => value as i64,
+        PointOpt::Sep => 0,
+    }
+}
+
+/// Read all fields of message.
+pub fn visit_app_msg(msg: &AppMsg) -> i64 {
+    match msg.as_ref() {
+        AppMsgRef::KeepAlive { seq } => *seq as i64,
+        AppMsgRef::DoUpdate { value } => u8::from(*value) as i64,
+        AppMsgRef::AoState { enable } | AppMsgRef::AoReadbackState { enable } => {
+            enable.to_native() as i64
+        }
+        AppMsgRef::AoData { points } => points.iter().map(point_value).sum(),
+        AppMsgRef::AoAdd { value } => *value as i64,
+        AppMsgRef::StatsReset { mask } => *mask as i64,
+        AppMsgRef::Goodbye | AppMsgRef::Hello => 0,
+    }
+}
+
+/// Read all fields of message.
+pub fn visit_mcu_msg(msg: &McuMsg) -> i64 {
+    match msg.as_ref() {
+        McuMsgRef::DiUpdate { value } => u8::from(*value) as i64,
+        McuMsgRef::AoRequest { count } => *count as i64,
+        McuMsgRef::AiData { points } => points.iter().flatten().map(point_value).sum(),
+        McuMsgRef::Error { code, message } => *code as i64 + message.len() as i64,
+        McuMsgRef::Debug { message } => String::from_utf8_lossy(message.as_slice()).len() as i64,
+        McuMsgRef::AoReadback { points } => points.iter().map(point_value).sum(),
+        McuMsgRef::SkifioState { temp, status } => *temp as i64 + *status as i64,
+        McuMsgRef::KeepAliveAck { seq } => *seq as i64,
+        McuMsgRef::StateSync {
+            di,
+            do_,
+            ao_enabled,
+            ao_add,
+        } => {
+            u8::from(*di) as i64
+                + u8::from(*do_) as i64
+                + ao_enabled.to_native() as i64
+                + *ao_add as i64
+        }
+        McuMsgRef::LoopStats {
+            cycle,
+            wait_ready,
+            cycle_max,
+            wait_ready_max,
+        } => {
+            cycle
+                .iter()
+                .chain(wait_ready)
+                .map(|&c| c as i64)
+                .sum::<i64>()
+                + *cycle_max as i64
+                + *wait_ready_max as i64
+        }
+        McuMsgRef::TaskStats {
+            run_time_freq,
+            tasks,
+        } => {
+            let usage = tasks.iter().map(|task| {
+                String::from_utf8_lossy(&task.name).len() as i64
+                    + task.run_time as i64
+                    + task.stack_free as i64
+            });
+            *run_time_freq as i64 + usage.sum::<i64>()
+        }
+        McuMsgRef::StatsCounters {
+            sync,
+            samples,
+            crc_errors,
+            ao_lost,
+            time_us,
+        } => {
+            *sync as i64 + *samples as i64 + *crc_errors as i64 + *ao_lost as i64 + *time_us as i64
+        }
+    }
+}
+
+/// Validate `data` as a single IOC message.
+pub fn parse_app_msg(data: &[u8]) {
+    let buffer = Buffer::copy_from(data);
+    if let Ok(msg) = AppMsg::from_bytes(&buffer[..data.len()]) {
+        assert!(msg.size() <= data.len());
+        black_box(visit_app_msg(msg));
+    }
+}
+
+/// Validate `data` as a single MCU message.
+pub fn parse_mcu_msg(data: &[u8]) {
+    let buffer = Buffer::copy_from(data);
+    if let Ok(msg) = McuMsg::from_bytes(&buffer[..data.len()]) {
+        assert!(msg.size() <= data.len());
+        black_box(visit_mcu_msg(msg));
+    }
+}
+
+/// Feed `data` to MCU as a byte stream received by fake channel.
+///
+/// Malformed messages are skipped the same way MCU tasks do.
+pub fn mcu_read(data: &[u8]) {
+    if data.len() > MAX_STREAM_LEN {
+        return;
+    }
+    let (stream, mut peer) = UnixStream::pair().unwrap();
+    // Whole input is written before reading, so that reader never closes connection with pending writes.
+    peer.write_all(data).unwrap();
+    drop(peer);
+
+    // The only connection, accepting the next one fails.
+    let (sender, receiver) = mpsc::channel();
+    sender.send(stream).unwrap();
+    drop(sender);
+
+    let (channel, _) = endpoints(receiver, 0);
+    let mut reader = Reader::<AppMsg>::new(channel, None);
+    loop {
+        match reader.read_message() {
+            Ok(msg) => {
+                black_box(visit_app_msg(&msg));
+            }
+            Err(Error {
+                kind: ErrorKind::Disconnected,
+                ..
+            }) => break,
+            Err(_) => continue,
+        }
+    }
+}
+
+/// Feed `data` to IOC as a byte stream received by fake channel.
+///
+/// Parsing errors are skipped, other errors stop reading as they cause IOC to reconnect.
+pub fn app_read(data: &[u8]) {
+    let mut reader = MsgReader::<McuMsg, _>::new(FramedRead::new(data), MAX_MCU_MSG_LEN);
+    block_on(async {
+        loop {
+            match reader.read_message().await {
+                Ok(msg) => {
+                    black_box(visit_mcu_msg(msg));
+                }
+                Err(ReadError::Parse(_)) => continue,
+                Err(ReadError::Io(_) | ReadError::Eof) => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,424 @@
+//! Messages are built from arbitrary field values, parsed back and compared field by field.
+
+use crate::Buffer;
+use arbitrary::Arbitrary;
+use common::{
+    config::{AI_COUNT, LOOP_HIST_BINS, MAX_APP_MSG_LEN, MAX_MCU_MSG_LEN},
+    protocol::{
+        self as proto, AppMsg, AppMsgMut, AppMsgRef, McuMsg, McuMsgMut, McuMsgRef, TaskUsage,
+        TASK_NAME_LEN,
+    },
+    values::{Bits, Point, Uv},
+};
+use flatty::{flat_vec, portable::Bool, prelude::*, traits::FlatBase};
+
+#[derive(Arbitrary, Debug)]
+pub enum AppMsgInput {
+    KeepAlive { seq: u32 },
+    DoUpdate { value: u8 },
+    AoState { enable: bool },
+    AoData { points: Vec<Uv> },
+    AoAdd { value: Uv },
+    StatsReset { mask: u32 },
+    AoReadbackState { enable: bool },
+    Goodbye,
+    Hello,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum McuMsgInput {
+    DiUpdate {
+        value: u8,
+    },
+    AoRequest {
+        count: u32,
+    },
+    AiData {
+        points: Vec<[Uv; AI_COUNT]>,
+    },
+    Error {
+        code: u8,
+        message: Vec<u8>,
+    },
+    Debug {
+        message: Vec<u8>,
+    },
+    AoReadback {
+        points: Vec<Uv>,
+    },
+    SkifioState {
+        temp: i8,
+        status: u8,
+    },
+    KeepAliveAck {
+        seq: u32,
+    },
+    StateSync {
+        di: u8,
+        do_: u8,
+        ao_enabled: bool,
+        ao_add: Uv,
+    },
+    LoopStats {
+        cycle: [u32; LOOP_HIST_BINS],
+        wait_ready: [u32; LOOP_HIST_BINS],
+        cycle_max: u32,
+        wait_ready_max: u32,
+    },
+    TaskStats {
+        run_time_freq: u32,
+        tasks: Vec<([u8; TASK_NAME_LEN], u32, u32)>,
+    },
+    StatsCounters {
+        sync: u32,
+        samples: u32,
+        crc_errors: u32,
+        ao_lost: u32,
+        time_us: u32,
+    },
+}
+
+/// Drop bits that don't fit.
+fn bits<const N: usize>(value: u8) -> Bits<N> {
+    Bits::try_from(value & (u8::MAX >> (8 - N))).unwrap()
+}
+
+fn task_usage((name, run_time, stack_free): ([u8; TASK_NAME_LEN], u32, u32)) -> TaskUsage {
+    TaskUsage {
+        name,
+        run_time,
+        stack_free,
+    }
+}
+
+/// Check that `points` is the longest prefix of `expected` that fits into message.
+fn assert_prefix<T: PartialEq + core::fmt::Debug>(points: &[T], expected: &[T], capacity: usize) {
+    assert_eq!(points.len(), expected.len().min(capacity));
+    assert_eq!(points, &expected[..points.len()]);
+}
+
+pub fn app_msg(input: &AppMsgInput) {
+    let mut buffer = Buffer::new(MAX_APP_MSG_LEN);
+    let (size, capacity) = {
+        let msg = match input {
+            AppMsgInput::KeepAlive { seq } => {
+                AppMsg::new_in_place(&mut buffer, proto::AppMsgInitKeepAlive { seq: *seq })
+            }
+            AppMsgInput::DoUpdate { value } => AppMsg::new_in_place(
+                &mut buffer,
+                proto::AppMsgInitDoUpdate {
+                    value: bits(*value),
+                },
+            ),
+            AppMsgInput::AoState { enable } => AppMsg::new_in_place(
+                &mut buffer,
+                proto::AppMsgInitAoState {
+                    enable: Bool::from_native(*enable),
+                },
+            ),
+            AppMsgInput::AoData { .. } => AppMsg::new_in_place(
+                &mut buffer,
+                proto::AppMsgInitAoData {
+                    points: flat_vec![],
+                },
+            ),
+            AppMsgInput::AoAdd { value } => {
+                AppMsg::new_in_place(&mut buffer, proto::AppMsgInitAoAdd { value: *value })
+            }
+            AppMsgInput::StatsReset { mask } => {
+                AppMsg::new_in_place(&mut buffer, proto::AppMsgInitStatsReset { mask: *mask })
+            }
+            AppMsgInput::AoReadbackState { enable } => AppMsg::new_in_place(
+                &mut buffer,
+                proto::AppMsgInitAoReadbackState {
+                    enable: Bool::from_native(*enable),
+                },
+            ),
+            AppMsgInput::Goodbye => AppMsg::new_in_place(&mut buffer, proto::AppMsgInitGoodbye),
+            AppMsgInput::Hello => AppMsg::new_in_place(&mut buffer, proto::AppMsgInitHello),
+        }
+        .unwrap();
+        let mut capacity = 0;
+        if let (AppMsgInput::AoData { points: src }, AppMsgMut::AoData { points }) =
+            (input, msg.as_mut())
+        {
+            capacity = points.capacity();
+            points.extend_from_iter(src.iter().map(|x| Point::from_uv(*x)));
+        }
+        (msg.size(), capacity)
+    };
+
+    let msg = AppMsg::from_bytes(&buffer[..size]).unwrap();
+    assert_eq!(msg.size(), size);
+    match (input, msg.as_ref()) {
+        (AppMsgInput::KeepAlive { seq }, AppMsgRef::KeepAlive { seq: value }) => {
+            assert_eq!(seq, value)
+        }
+        (AppMsgInput::DoUpdate { value }, AppMsgRef::DoUpdate { value: do_ }) => {
+            assert_eq!(bits(*value), *do_)
+        }
+        (AppMsgInput::AoState { enable }, AppMsgRef::AoState { enable: value })
+        | (AppMsgInput::AoReadbackState { enable }, AppMsgRef::AoReadbackState { enable: value }) =>
+        {
+            assert_eq!(*enable, value.to_native())
+        }
+        (AppMsgInput::AoData { points: src }, AppMsgRef::AoData { points }) => {
+            let expected = src.iter().map(|x| Point::from_uv(*x)).collect::<Vec<_>>();
+            assert_prefix(points.as_slice(), &expected, capacity);
+        }
+        (AppMsgInput::AoAdd { value }, AppMsgRef::AoAdd { value: add }) => assert_eq!(value, add),
+        (AppMsgInput::StatsReset { mask }, AppMsgRef::StatsReset { mask: value }) => {
+            assert_eq!(mask, value)
+        }
+        (AppMsgInput::Goodbye, AppMsgRef::Goodbye) | (AppMsgInput::Hello, AppMsgRef::Hello) => (),
+        (input, _) => panic!("Variant mismatch: {:?}", input),
+    }
+}
+
+pub fn mcu_msg(input: &McuMsgInput) {
+    let mut buffer = Buffer::new(MAX_MCU_MSG_LEN);
+    let (size, capacity) = {
+        let msg = match input {
+            McuMsgInput::DiUpdate { value } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitDiUpdate {
+                    value: bits(*value),
+                },
+            ),
+            McuMsgInput::AoRequest { count } => {
+                McuMsg::new_in_place(&mut buffer, proto::McuMsgInitAoRequest { count: *count })
+            }
+            McuMsgInput::AiData { .. } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitAiData {
+                    points: flat_vec![],
+                },
+            ),
+            McuMsgInput::Error { code, .. } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitError {
+                    code: *code,
+                    message: flat_vec![],
+                },
+            ),
+            McuMsgInput::Debug { .. } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitDebug {
+                    message: flat_vec![],
+                },
+            ),
+            McuMsgInput::AoReadback { .. } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitAoReadback {
+                    points: flat_vec![],
+                },
+            ),
+            McuMsgInput::SkifioState { temp, status } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitSkifioState {
+                    temp: *temp,
+                    status: *status,
+                },
+            ),
+            McuMsgInput::KeepAliveAck { seq } => {
+                McuMsg::new_in_place(&mut buffer, proto::McuMsgInitKeepAliveAck { seq: *seq })
+            }
+            McuMsgInput::StateSync {
+                di,
+                do_,
+                ao_enabled,
+                ao_add,
+            } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitStateSync {
+                    di: bits(*di),
+                    do_: bits(*do_),
+                    ao_enabled: Bool::from_native(*ao_enabled),
+                    ao_add: *ao_add,
+                },
+            ),
+            McuMsgInput::LoopStats {
+                cycle,
+                wait_ready,
+                cycle_max,
+                wait_ready_max,
+            } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitLoopStats {
+                    cycle: *cycle,
+                    wait_ready: *wait_ready,
+                    cycle_max: *cycle_max,
+                    wait_ready_max: *wait_ready_max,
+                },
+            ),
+            McuMsgInput::TaskStats { run_time_freq, .. } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitTaskStats {
+                    run_time_freq: *run_time_freq,
+                    tasks: flat_vec![],
+                },
+            ),
+            McuMsgInput::StatsCounters {
+                sync,
+                samples,
+                crc_errors,
+                ao_lost,
+                time_us,
+            } => McuMsg::new_in_place(
+                &mut buffer,
+                proto::McuMsgInitStatsCounters {
+                    sync: *sync,
+                    samples: *samples,
+                    crc_errors: *crc_errors,
+                    ao_lost: *ao_lost,
+                    time_us: *time_us,
+                },
+            ),
+        }
+        .unwrap();
+        let capacity = match (input, msg.as_mut()) {
+            (McuMsgInput::AiData { points: src }, McuMsgMut::AiData { points }) => {
+                points.extend_from_iter(src.iter().map(|xs| xs.map(Point::from_uv)));
+                points.capacity()
+            }
+            (McuMsgInput::Error { message: src, .. }, McuMsgMut::Error { message, .. })
+            | (McuMsgInput::Debug { message: src }, McuMsgMut::Debug { message }) => {
+                message.extend_from_iter(src.iter().copied());
+                message.capacity()
+            }
+            (McuMsgInput::AoReadback { points: src }, McuMsgMut::AoReadback { points }) => {
+                points.extend_from_iter(src.iter().map(|x| Point::from_uv(*x)));
+                points.capacity()
+            }
+            (McuMsgInput::TaskStats { tasks: src, .. }, McuMsgMut::TaskStats { tasks, .. }) => {
+                tasks.extend_from_iter(src.iter().copied().map(task_usage));
+                tasks.capacity()
+            }
+            _ => 0,
+        };
+        (msg.size(), capacity)
+    };
+
+    let msg = McuMsg::from_bytes(&buffer[..size]).unwrap();
+    assert_eq!(msg.size(), size);
+    match (input, msg.as_ref()) {
+        (McuMsgInput::DiUpdate { value }, McuMsgRef::DiUpdate { value: di }) => {
+            assert_eq!(bits(*value), *di)
+        }
+        (McuMsgInput::AoRequest { count }, McuMsgRef::AoRequest { count: value }) => {
+            assert_eq!(count, value)
+        }
+        (McuMsgInput::AiData { points: src }, McuMsgRef::AiData { points }) => {
+            let expected = src
+                .iter()
+                .map(|xs| xs.map(Point::from_uv))
+                .collect::<Vec<_>>();
+            assert_prefix(points.as_slice(), &expected, capacity);
+        }
+        (
+            McuMsgInput::Error { code, message: src },
+            McuMsgRef::Error {
+                code: value,
+                message,
+            },
+        ) => {
+            assert_eq!(code, value);
+            assert_prefix(message.as_slice(), src, capacity);
+        }
+        (McuMsgInput::Debug { message: src }, McuMsgRef::Debug { message }) => {
+            assert_prefix(message.as_slice(), src, capacity);
+        }
+        (McuMsgInput::AoReadback { points: src }, McuMsgRef::AoReadback { points }) => {
+            let expected = src.iter().map(|x| Point::from_uv(*x)).collect::<Vec<_>>();
+            assert_prefix(points.as_slice(), &expected, capacity);
+        }
+        (
+            McuMsgInput::SkifioState { temp, status },
+            McuMsgRef::SkifioState {
+                temp: temp_value,
+                status: status_value,
+            },
+        ) => {
+            assert_eq!(temp, temp_value);
+            assert_eq!(status, status_value);
+        }
+        (McuMsgInput::KeepAliveAck { seq }, McuMsgRef::KeepAliveAck { seq: value }) => {
+            assert_eq!(seq, value)
+        }
+        (
+            McuMsgInput::StateSync {
+                di,
+                do_,
+                ao_enabled,
+                ao_add,
+            },
+            McuMsgRef::StateSync {
+                di: di_value,
+                do_: do_value,
+                ao_enabled: ao_enabled_value,
+                ao_add: ao_add_value,
+            },
+        ) => {
+            assert_eq!(bits(*di), *di_value);
+            assert_eq!(bits(*do_), *do_value);
+            assert_eq!(*ao_enabled, ao_enabled_value.to_native());
+            assert_eq!(ao_add, ao_add_value);
+        }
+        (
+            McuMsgInput::LoopStats {
+                cycle,
+                wait_ready,
+                cycle_max,
+                wait_ready_max,
+            },
+            McuMsgRef::LoopStats {
+                cycle: cycle_value,
+                wait_ready: wait_ready_value,
+                cycle_max: cycle_max_value,
+                wait_ready_max: wait_ready_max_value,
+            },
+        ) => {
+            assert_eq!(cycle, cycle_value);
+            assert_eq!(wait_ready, wait_ready_value);
+            assert_eq!(cycle_max, cycle_max_value);
+            assert_eq!(wait_ready_max, wait_ready_max_value);
+        }
+        (
+            McuMsgInput::TaskStats {
+                run_time_freq,
+                tasks: src,
+            },
+            McuMsgRef::TaskStats {
+                run_time_freq: freq_value,
+                tasks,
+            },
+        ) => {
+            assert_eq!(run_time_freq, freq_value);
+            let expected = src.iter().copied().map(task_usage).collect::<Vec<_>>();
+            assert_prefix(tasks.as_slice(), &expected, capacity);
+        }
+        (
+            McuMsgInput::StatsCounters {
+                sync,
+                samples,
+                crc_errors,
+                ao_lost,
+                time_us,
+            },
+            McuMsgRef::StatsCounters {
+                sync: sync_value,
+                samples: samples_value,
+                crc_errors: crc_errors_value,
+                ao_lost: ao_lost_value,
+                time_us: time_us_value,
+            },
+        ) => {
+            assert_eq!(sync, sync_value);
+            assert_eq!(samples, samples_value);
+            assert_eq!(crc_errors, crc_errors_value);
+            assert_eq!(ao_lost, ao_lost_value);
+            assert_eq!(time_us, time_us_value);
+        }
+        (input, _) => panic!("Variant mismatch: {:?}", input),
+    }
+}
@@ -9,13 +9,15 @@ from vortex.tasks.epics.epics_base import EpicsBaseHost, EpicsBaseCross
 from vortex.tasks.rust import RustcHost, RustcCross
 
 from .ioc import AppIocHost, AppIocCross
-from .user import AppReal, AppFake
+from .user import AppReal, AppFake, AppLoopback
 
 
 class AppGroupHost(ComponentGroup):
     def __init__(self, rustc: RustcHost, epics_base: EpicsBaseHost, src: Path, dst: TargetPath) -> None:
         self.user = AppFake(rustc, src / "user", dst / "user")
         self.ioc = AppIocHost(src / "ioc", dst / "ioc", epics_base, dylibs=[self.user])
+        self.loopback = AppLoopback(rustc, src / "user", dst / "loopback/user")
+        self.loopback_ioc = AppIocHost(src / "ioc", dst / "loopback/ioc", epics_base, dylibs=[self.loopback])
 
     @task
     def build(self, ctx: Context) -> None:
@@ -25,6 +27,15 @@ class AppGroupHost(ComponentGroup):
     def run(self, ctx: Context) -> None:
         self.ioc.run(ctx)
 
+    @task
+    def build_loopback(self, ctx: Context) -> None:
+        self.loopback_ioc.build(ctx)
+
+    @task
+    def run_loopback(self, ctx: Context) -> None:
+        """Run IOC with fake MCU and SkifIO emulator linked in, no separate fakedev needed."""
+        self.loopback_ioc.run(ctx)
+
 
 class AppGroupCross(ComponentGroup):
     def __init__(self, rustc: RustcCross, epics_base: EpicsBaseCross, src: Path, dst: TargetPath) -> None:
@@ -22,3 +22,10 @@ class AppFake(AbstractApp):
 class AppReal(AbstractApp):
     def __init__(self, rustc: RustcCross, src: Path, dst: TargetPath):
         super().__init__(rustc, src, dst, features=["real"])
+
+
+class AppLoopback(AbstractApp):
+    """App with fake MCU and SkifIO emulator linked in."""
+
+    def __init__(self, rustc: RustcHost, src: Path, dst: TargetPath):
+        super().__init__(rustc, src, dst, features=["loopback"])
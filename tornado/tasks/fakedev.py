@@ -17,6 +17,8 @@ class Fakedev(Cargo):
             src,
             dst,
             rustc,
+            # Tests are run against IOC started alongside.
+            features=["ioc"],
             # run_mode=RunMode.PROFILER,
         )
         self.ioc = ioc
@@ -55,6 +57,6 @@ class Fakedev(Cargo):
 
         @task
         def fake_mcu(ctx: Context) -> None:
-            super(Fakedev, self).run(ctx, bin="test")
+            super(Fakedev, self).test(ctx)
 
         ConcurrentTaskList(fake_ioc, fake_mcu)(ctx)